@@ -1,22 +1,28 @@
+use std::thread;
+
 use bend::{
     diagnostics::{Diagnostics, DiagnosticsConfig},
     fun::{Book, Term},
     CompileOpts, RunOpts,
 };
 
+// Reading back the result of HVM is recursive, so deep values need a stack
+// much larger than the one of the Python thread.
+const RUN_STACK_SIZE: usize = 1 << 30;
+
 pub fn run(book: &Book) -> Option<(Term, String, Diagnostics)> {
     let run_opts = RunOpts::default();
     let compile_opts = CompileOpts::default().set_all();
     let diagnostics_cfg = DiagnosticsConfig::default();
     let args = None;
+    let book = book.clone();
 
-    bend::run_book(
-        book.clone(),
-        run_opts,
-        compile_opts,
-        diagnostics_cfg,
-        args,
-        "run",
-    )
-    .unwrap()
+    thread::Builder::new()
+        .stack_size(RUN_STACK_SIZE)
+        .spawn(move || {
+            bend::run_book(book, run_opts, compile_opts, diagnostics_cfg, args, "run").unwrap()
+        })
+        .unwrap()
+        .join()
+        .unwrap()
 }
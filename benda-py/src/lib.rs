@@ -1,19 +1,31 @@
 use bend::imp;
-use num_traits::ToPrimitive;
-use parser::Parser;
+use parser::{modules::load_modules, Parser};
 use pyo3::{
+    exceptions::{PyOSError, PySyntaxError, PyTypeError},
     prelude::*,
-    types::{PyDict, PyFunction, PyString, PyTuple},
+    types::{PyDict, PyFunction, PyTuple},
+};
+use rustpython_parser::{
+    ast::{Mod, Stmt},
+    parse, Mode,
 };
-use rustpython_parser::{parse, Mode};
 use types::tree::Tree;
 use types::{
-    extract_type,
+    bignum, extract_type,
+    f24::f24,
+    i24::i24,
+    modules::set_main_module,
+    names::escape,
+    overflow::{self, Overflow},
+    readback::Readback,
     tree::{Leaf, Node},
     u24::u24,
+    user_adt::{register_adt, user_adt_defs, Term},
 };
 mod benda_ffi;
 mod parser;
+#[cfg(test)]
+mod tests;
 mod types;
 
 #[pyfunction]
@@ -21,99 +33,230 @@ fn switch() -> PyResult<String> {
     Ok("Ok".to_string())
 }
 
-#[pyclass(name = "bjit")]
+// Used as `@bjit`, or with options like `@bjit(bignum=True)`. With `bignum`
+// the Python ints given to the function are arbitrary-precision integers,
+// otherwise the ones that don't fit in 24 bits raise an `OverflowError`,
+// with `overflow="error"`, give a warning, with "warn", or are just wrapped
+// around, with "wrap".
+#[pyclass(module = "benda", name = "bjit")]
 pub struct PyBjit {
-    wraps: Py<PyAny>,
+    wraps: Option<Py<PyAny>>,
+    bignum: bool,
+    overflow: Overflow,
 }
 
 #[pymethods]
 impl PyBjit {
+    // The name of the decorated function, used to reference it from Bend.
+    #[getter]
+    fn __name__(&self, py: Python<'_>) -> PyResult<String> {
+        match &self.wraps {
+            Some(wraps) => Ok(wraps.bind(py).getattr("__name__")?.to_string()),
+            None => Err(PyTypeError::new_err("bjit was not given a function")),
+        }
+    }
+
+    // The decorated function, like `functools.wraps` does.
+    #[getter]
+    fn __wrapped__(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        match &self.wraps {
+            Some(wraps) => Ok(wraps.clone_ref(py)),
+            None => Err(PyTypeError::new_err("bjit was not given a function")),
+        }
+    }
+
+    // As a method, the object it is read from is given as its first argument.
+    fn __get__(
+        slf: Py<Self>,
+        py: Python<'_>,
+        obj: Option<&Bound<'_, PyAny>>,
+        _objtype: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Py<PyAny>> {
+        match obj {
+            Some(obj) if !obj.is_none() => {
+                let method = py.import_bound("types")?.getattr("MethodType")?;
+                Ok(method.call1((slf, obj))?.unbind())
+            }
+            _ => Ok(slf.into_any()),
+        }
+    }
+
     #[new]
-    fn __new__(wraps: Py<PyAny>) -> Self {
-        PyBjit { wraps }
+    #[pyo3(signature = (wraps = None, *, bignum = false, overflow = Overflow::Error))]
+    fn __new__(wraps: Option<Py<PyAny>>, bignum: bool, overflow: Overflow) -> Self {
+        PyBjit {
+            wraps,
+            bignum,
+            overflow,
+        }
     }
-    #[pyo3(signature = (*args, **_kwargs))]
+    #[pyo3(signature = (*args, **kwargs))]
     fn __call__(
         &self,
         py: Python<'_>,
         args: &Bound<'_, PyTuple>,
-        _kwargs: Option<&Bound<'_, PyDict>>,
+        kwargs: Option<&Bound<'_, PyDict>>,
     ) -> PyResult<Py<PyAny>> {
-        let arg_names_temp: Bound<PyAny>;
-
-        let (name, filename, arg_names, argcount) =
-            match self.wraps.downcast_bound::<PyFunction>(py) {
-                Ok(inner) => {
-                    let name = inner.getattr("__name__").unwrap();
-                    let code = inner.getattr("__code__").unwrap();
-                    let filename = code.getattr("co_filename").unwrap();
-
-                    arg_names_temp = code.getattr("co_varnames").unwrap();
-                    let arg_names = arg_names_temp.downcast::<PyTuple>().unwrap();
-                    let argcount = code
-                        .getattr("co_argcount")
-                        .unwrap()
-                        .to_string()
-                        .parse::<u32>()
-                        .unwrap();
-
-                    (name, filename, arg_names, argcount)
-                }
-                Err(_) => todo!(),
+        // With options, the decorator is called first and then given the function.
+        let Some(wraps) = &self.wraps else {
+            let bjit = PyBjit {
+                wraps: Some(args.get_item(0)?.unbind()),
+                bignum: self.bignum,
+                overflow: self.overflow,
             };
+            return Ok(Py::new(py, bjit)?.into_any());
+        };
+
+        let module = wraps.bind(py).getattr("__module__")?.to_string();
+        let _options = CallOptions::set(py, self.bignum, self.overflow, &module);
 
-        let mut arg_list: Vec<String> = vec![];
+        let (name, filename, globals) = match wraps.downcast_bound::<PyFunction>(py) {
+            Ok(inner) => {
+                let name = inner.getattr("__qualname__")?;
+                let code = inner.getattr("__code__")?;
+                let filename = code.getattr("co_filename")?;
+                let globals = inner.getattr("__globals__")?.downcast_into::<PyDict>()?;
 
-        for (index, arg) in arg_names.iter().enumerate() {
-            if index >= argcount.to_usize().unwrap() {
-                break;
+                (name, filename, globals)
             }
+            Err(_) => {
+                return Err(PyTypeError::new_err(format!(
+                    "bjit can only compile functions written in Python, not '{}'",
+                    wraps.bind(py).get_type().name()?
+                )))
+            }
+        };
 
-            arg_list.push(arg.to_string());
-        }
+        // The arguments are bound like Python does, so the keyword arguments
+        // and default values are given to Bend in the order of the parameters.
+        let signature = py
+            .import_bound("inspect")?
+            .call_method1("signature", (wraps,))?;
+        let bound = signature.call_method("bind", args, kwargs)?;
+        bound.call_method0("apply_defaults")?;
+        let arguments = bound.getattr("arguments")?;
 
         let mut parsed_types: Vec<(String, imp::Expr)> = vec![];
 
-        for (index, arg) in args.downcast::<PyTuple>().unwrap().iter().enumerate() {
-            parsed_types.push((
-                arg_list.get(index).unwrap().to_string(),
-                extract_type(arg).unwrap(),
-            ));
+        for (name, arg) in arguments.downcast::<PyDict>()?.iter() {
+            let name = name.to_string();
+            overflow::set_argument(py, &name);
+            parsed_types.push((name, extract_type(arg)?));
         }
 
-        let code = std::fs::read_to_string(filename.to_string()).unwrap();
-        let module = parse(code.as_str(), Mode::Module, "main.py").unwrap();
+        // The source of the module is parsed, so it must be in a file.
+        let code = std::fs::read_to_string(filename.to_string()).map_err(|err| {
+            PyOSError::new_err(format!(
+                "Could not read the source of '{}' from '{}': {}",
+                name, filename, err
+            ))
+        })?;
+        let module = parse(code.as_str(), Mode::Module, "main.py")
+            .map_err(|err| PySyntaxError::new_err(err.to_string()))?;
 
-        let mut val: Option<Bound<PyString>> = None;
+        let mut val: Option<Py<PyAny>> = None;
 
         match module {
-            rustpython_parser::ast::Mod::Module(mods) => {
-                for (index, stmt) in mods.body.iter().enumerate() {
-                    if let rustpython_parser::ast::Stmt::FunctionDef(fun_def) = stmt {
-                        if fun_def.name == name.to_string() {
-                            let mut parser =
-                                Parser::new(mods.body.clone(), index, parsed_types.clone());
-                            let return_val = parser.parse(fun_def.name.as_ref(), &[]);
-                            val = Some(PyString::new_bound(py, return_val.as_str()));
-                            break;
-                        }
-                    }
+            Mod::Module(mods) => {
+                let name = name.to_string();
+                if has_def(&mods.body, &name) {
+                    // Methods are named after their class, like `Circle/area`.
+                    let parts: Vec<String> = name.split('.').map(escape).collect();
+                    let fun_name = parts.join("/");
+
+                    let modules = load_modules(py, mods.body.clone(), &globals)?;
+                    let mut parser = Parser::new(
+                        modules,
+                        std::mem::take(&mut parsed_types),
+                        user_adt_defs(py)?,
+                        self.bignum,
+                        self.overflow,
+                    );
+                    let return_val = parser.parse(&fun_name)?;
+                    let fun = wraps.downcast_bound::<PyFunction>(py)?;
+                    let readback = Readback::new(
+                        py,
+                        parser.book(),
+                        parser.ctr_aliases(),
+                        fun,
+                        parser.return_hint(&fun_name),
+                    )?;
+                    let bignum = parser.returns_bignum(&fun_name);
+                    val = Some(readback.to_python(&return_val, bignum)?);
                 }
             }
-            _ => unimplemented!(),
+            _ => return Err(PySyntaxError::new_err("Could not parse the module")),
+        }
+
+        val.ok_or_else(|| {
+            PyTypeError::new_err(format!(
+                "'{}' must be defined at the top of its module, or in a class at the top of it, to be compiled",
+                name
+            ))
+        })
+    }
+}
+
+// The options of a call, kept where the values it is given are converted.
+// Converting a value may call other `@bjit` functions, so the options of the
+// caller are put back when the call returns.
+struct CallOptions<'py> {
+    py: Python<'py>,
+    bignum: bool,
+    overflow: Overflow,
+    argument: String,
+    main_module: String,
+}
+
+impl<'py> CallOptions<'py> {
+    fn set(py: Python<'py>, bignum: bool, overflow: Overflow, main_module: &str) -> Self {
+        Self {
+            py,
+            bignum: bignum::set_enabled(py, bignum),
+            overflow: overflow::set_policy(py, overflow),
+            argument: overflow::set_argument(py, ""),
+            main_module: set_main_module(py, main_module),
         }
+    }
+}
 
-        Ok(val.unwrap().into())
+impl Drop for CallOptions<'_> {
+    fn drop(&mut self) {
+        bignum::set_enabled(self.py, self.bignum);
+        overflow::set_policy(self.py, self.overflow);
+        overflow::set_argument(self.py, &self.argument);
+        set_main_module(self.py, &self.main_module);
     }
 }
 
+// Whether a function is defined at the top of the module or, for a method,
+// like `Circle.area`, in the body of its class.
+fn has_def(stmts: &[Stmt], qualname: &str) -> bool {
+    let (class, name) = match qualname.split_once('.') {
+        Some((class, name)) => (Some(class), name),
+        None => (None, qualname),
+    };
+
+    stmts.iter().any(|stmt| match (stmt, class) {
+        (Stmt::FunctionDef(fun_def), None) => fun_def.name.as_str() == name,
+        (Stmt::ClassDef(class_def), Some(class)) => {
+            class_def.name.as_str() == class && has_def(&class_def.body, name)
+        }
+        _ => false,
+    })
+}
+
 #[pymodule]
 fn benda(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(switch, m)?)?;
+    m.add_function(wrap_pyfunction!(register_adt, m)?)?;
     m.add_class::<PyBjit>()?;
     m.add_class::<u24>()?;
+    m.add_class::<i24>()?;
+    m.add_class::<f24>()?;
     m.add_class::<Tree>()?;
     m.add_class::<Node>()?;
     m.add_class::<Leaf>()?;
+    m.add_class::<Term>()?;
     Ok(())
 }
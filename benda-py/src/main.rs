@@ -1,11 +1,7 @@
-mod parser;
-
 use pyo3::prelude::*;
 
 use rustpython_parser::{parse, Mode};
 
-mod benda_ffi;
-
 fn main() -> PyResult<()> {
     let filename = String::from("main.py");
 
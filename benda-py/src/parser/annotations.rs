@@ -0,0 +1,235 @@
+use rustpython_ast::Visitor;
+use rustpython_parser::{
+    ast::{
+        Constant, Expr as rExpr, ExprConstant, Mod, Operator as rOperator, Stmt as rStmt,
+        StmtAnnAssign, StmtFunctionDef,
+    },
+    parse, Mode,
+};
+
+// The annotation written in a string, like the forward reference `"Node"`.
+fn unquote(annotation: &rExpr) -> Option<rExpr> {
+    let rExpr::Constant(c) = annotation else {
+        return None;
+    };
+    let Constant::Str(code) = &c.value else {
+        return None;
+    };
+
+    match parse(code, Mode::Expression, "<annotation>") {
+        Ok(Mod::Expression(expr)) => Some(*expr.body),
+        _ => None,
+    }
+}
+
+// The name of a `typing` construct, with or without its module.
+fn typing_name(expr: &rExpr) -> Option<&str> {
+    match expr {
+        rExpr::Name(name) => Some(name.id.as_str()),
+        rExpr::Attribute(att) => Some(att.attr.as_str()),
+        _ => None,
+    }
+}
+
+pub fn is_none(expr: &rExpr) -> bool {
+    matches!(expr, rExpr::Constant(c) if c.value.is_none())
+}
+
+// The types of a union, like `A | B`, `Union[A, B]` or `Optional[A]`, which
+// also has `None`. Other annotations are a union of themselves.
+pub fn union_members(annotation: &rExpr) -> Vec<rExpr> {
+    if let Some(annotation) = unquote(annotation) {
+        return union_members(&annotation);
+    }
+
+    match annotation {
+        rExpr::BinOp(bin) if bin.op == rOperator::BitOr => {
+            let mut members = union_members(&bin.left);
+            members.extend(union_members(&bin.right));
+            members
+        }
+        rExpr::Subscript(sub) => match typing_name(&sub.value) {
+            Some("Optional") => {
+                let mut members = union_members(&sub.slice);
+                members.retain(|member| !is_none(member));
+                members.push(rExpr::Constant(ExprConstant {
+                    range: sub.range,
+                    value: Constant::None,
+                    kind: None,
+                }));
+                members
+            }
+            Some("Union") => match sub.slice.as_ref() {
+                rExpr::Tuple(tup) => tup.elts.iter().flat_map(union_members).collect(),
+                member => union_members(member),
+            },
+            _ => vec![annotation.clone()],
+        },
+        _ => vec![annotation.clone()],
+    }
+}
+
+// The only type of an annotation besides `None`, like `int` for `int`,
+// `Optional[int]` or `int | None`.
+pub fn optional_type(annotation: &rExpr) -> Option<rExpr> {
+    let mut members = union_members(annotation);
+    members.retain(|member| !is_none(member));
+
+    match members.len() {
+        1 => members.pop(),
+        _ => None,
+    }
+}
+
+// Class variables, like `count: ClassVar[int] = 0`, are not dataclass fields.
+pub fn is_class_var(annotation: &rExpr) -> bool {
+    match annotation {
+        rExpr::Subscript(sub) => typing_name(&sub.value) == Some("ClassVar"),
+        annotation => typing_name(annotation) == Some("ClassVar"),
+    }
+}
+
+fn is_union(expr: &rExpr) -> bool {
+    if let Some(expr) = unquote(expr) {
+        return is_union(&expr);
+    }
+
+    match expr {
+        rExpr::BinOp(bin) => bin.op == rOperator::BitOr,
+        rExpr::Subscript(sub) => matches!(typing_name(&sub.value), Some("Union" | "Optional")),
+        _ => false,
+    }
+}
+
+// The name and value of a type alias, like `Shape = Circle | Square`,
+// `Shape: TypeAlias = Circle | Square` or `type Shape = Circle | Square`.
+// Only unions are aliases, other assignments are values. A plain assignment
+// is only an alias when all of its members are classes, since `X = A | B`
+// may as well be the bitwise or of two numbers.
+pub fn type_alias(stmt: &rStmt, is_class: impl Fn(&rExpr) -> bool) -> Option<(&str, &rExpr)> {
+    let (target, value) = match stmt {
+        rStmt::Assign(assign) => match assign.targets.as_slice() {
+            [target] => {
+                let members = union_members(&assign.value);
+                if !members
+                    .iter()
+                    .all(|member| is_none(member) || is_class(member))
+                {
+                    return None;
+                }
+                (target, assign.value.as_ref())
+            }
+            _ => return None,
+        },
+        rStmt::AnnAssign(assign) if typing_name(&assign.annotation) == Some("TypeAlias") => {
+            (assign.target.as_ref(), assign.value.as_deref()?)
+        }
+        rStmt::TypeAlias(alias) => (alias.name.as_ref(), alias.value.as_ref()),
+        _ => return None,
+    };
+
+    match target {
+        rExpr::Name(name) if is_union(value) => Some((name.id.as_str(), value)),
+        _ => None,
+    }
+}
+
+// The annotations of a module that allow `None`, like `Optional[Node]`, in
+// the fields of its classes and in the parameters, returned values and
+// variables of its functions.
+pub fn optional_annotations(stmts: &[rStmt]) -> Vec<rExpr> {
+    let mut finder = OptionalFinder { found: vec![] };
+    for stmt in stmts {
+        finder.visit_stmt(stmt.clone());
+    }
+    finder.found
+}
+
+struct OptionalFinder {
+    found: Vec<rExpr>,
+}
+
+impl OptionalFinder {
+    fn check(&mut self, annotation: &rExpr) {
+        if union_members(annotation).iter().any(is_none) {
+            self.found.push(annotation.clone());
+        }
+    }
+}
+
+impl Visitor for OptionalFinder {
+    fn visit_stmt_function_def(&mut self, node: StmtFunctionDef) {
+        let args = &node.args;
+        for arg in args
+            .posonlyargs
+            .iter()
+            .chain(&args.args)
+            .chain(&args.kwonlyargs)
+        {
+            if let Some(annotation) = &arg.def.annotation {
+                self.check(annotation);
+            }
+        }
+        if let Some(returns) = &node.returns {
+            self.check(returns);
+        }
+        self.generic_visit_stmt_function_def(node);
+    }
+
+    fn visit_stmt_ann_assign(&mut self, node: StmtAnnAssign) {
+        self.check(&node.annotation);
+        self.generic_visit_stmt_ann_assign(node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustpython_parser::{
+        ast::{Expr as rExpr, Mod},
+        parse, Mode,
+    };
+
+    use super::{is_none, optional_type, union_members};
+
+    fn annotation(code: &str) -> rExpr {
+        match parse(code, Mode::Expression, "<test>") {
+            Ok(Mod::Expression(expr)) => *expr.body,
+            _ => panic!("Could not parse '{}'", code),
+        }
+    }
+
+    fn name(expr: &rExpr) -> String {
+        match expr {
+            rExpr::Name(name) => name.id.to_string(),
+            expr if is_none(expr) => "None".to_string(),
+            _ => panic!("Not a name: {:?}", expr),
+        }
+    }
+
+    fn members(code: &str) -> Vec<String> {
+        union_members(&annotation(code)).iter().map(name).collect()
+    }
+
+    #[test]
+    fn union_members_of_every_spelling() {
+        assert_eq!(members("A | B"), ["A", "B"]);
+        assert_eq!(members("Union[A, B, C]"), ["A", "B", "C"]);
+        assert_eq!(members("typing.Union[A, B]"), ["A", "B"]);
+        assert_eq!(members("Optional[A]"), ["A", "None"]);
+        assert_eq!(members("Optional[A | B]"), ["A", "B", "None"]);
+        assert_eq!(members("'A | None'"), ["A", "None"]);
+        assert_eq!(members("A"), ["A"]);
+    }
+
+    #[test]
+    fn optional_type_is_the_only_member_besides_none() {
+        let optional = |code: &str| optional_type(&annotation(code)).map(|expr| name(&expr));
+
+        assert_eq!(optional("Optional[Node]").as_deref(), Some("Node"));
+        assert_eq!(optional("Node | None").as_deref(), Some("Node"));
+        assert_eq!(optional("Optional['Node']").as_deref(), Some("Node"));
+        assert_eq!(optional("Node").as_deref(), Some("Node"));
+        assert_eq!(optional("Union[A, B]"), None);
+        assert_eq!(optional("Optional[A | B]"), None);
+    }
+}
@@ -0,0 +1,265 @@
+use bend::{
+    fun::{parser::Indent, Op},
+    imp::{self, parser::PyParser, Expr},
+};
+use pyo3::{exceptions::PyTypeError, PyResult};
+
+use super::num_types::call;
+
+// The Bend functions used for the operations on bignums. A bignum is a list
+// with its sign (0 for positive, 1 for negative) followed by its limbs of 12
+// bits, from the least significant one. The limbs never end with a zero, so
+// zero is `[0]`. The magnitude functions (`mag_*`) work on the limbs alone.
+// A division by zero gives `[2]`, which the arithmetic operations give back
+// so it is raised as a `ZeroDivisionError` when it is returned.
+const DEFS: [&str; 22] = [
+    "benda/big/head(l):
+  match l:
+    case List/Nil:
+      return 0
+    case List/Cons:
+      return l.head
+",
+    "benda/big/tail(l):
+  match l:
+    case List/Nil:
+      return []
+    case List/Cons:
+      return l.tail
+",
+    // Puts a limb in front of others, without leaving a zero at the end.
+    "benda/big/cons(d, l):
+  match l:
+    case List/Nil:
+      if d == 0:
+        return []
+      else:
+        return [d]
+    case List/Cons:
+      return List/Cons(d, List/Cons(l.head, l.tail))
+",
+    "benda/big/make(s, m):
+  match m:
+    case List/Nil:
+      return [0]
+    case List/Cons:
+      return List/Cons(s, List/Cons(m.head, m.tail))
+",
+    "benda/big/from_u24(n):
+  return benda/big/make(0, benda/big/limbs(n))
+",
+    "benda/big/limbs(n):
+  if n == 0:
+    return []
+  else:
+    return List/Cons(n % 4096, benda/big/limbs(n / 4096))
+",
+    "benda/big/mag_add(a, b, c):
+  match a:
+    case List/Nil:
+      match b:
+        case List/Nil:
+          return benda/big/cons(c, [])
+        case List/Cons:
+          return benda/big/mag_add(List/Cons(b.head, b.tail), [], c)
+    case List/Cons:
+      s = a.head + benda/big/head(b) + c
+      return benda/big/cons(s % 4096, benda/big/mag_add(a.tail, benda/big/tail(b), s / 4096))
+",
+    // Subtracts with a borrow, `a` must not be smaller than `b`.
+    "benda/big/mag_sub(a, b, w):
+  match a:
+    case List/Nil:
+      return []
+    case List/Cons:
+      s = a.head + 4096 - benda/big/head(b) - w
+      return benda/big/cons(s % 4096, benda/big/mag_sub(a.tail, benda/big/tail(b), 1 - s / 4096))
+",
+    // Returns 0 when `a < b`, 1 when they are equal and 2 when `a > b`.
+    "benda/big/mag_cmp(a, b):
+  match a:
+    case List/Nil:
+      match b:
+        case List/Nil:
+          return 1
+        case List/Cons:
+          return 0
+    case List/Cons:
+      match b:
+        case List/Nil:
+          return 2
+        case List/Cons:
+          r = benda/big/mag_cmp(a.tail, b.tail)
+          if r == 1:
+            if a.head < b.head:
+              return 0
+            elif a.head > b.head:
+              return 2
+            else:
+              return 1
+          else:
+            return r
+",
+    "benda/big/mag_mul_digit(a, d, c):
+  match a:
+    case List/Nil:
+      return benda/big/cons(c, [])
+    case List/Cons:
+      s = a.head * d + c
+      return benda/big/cons(s % 4096, benda/big/mag_mul_digit(a.tail, d, s / 4096))
+",
+    "benda/big/mag_mul(a, b):
+  match a:
+    case List/Nil:
+      return []
+    case List/Cons:
+      rest = benda/big/cons(0, benda/big/mag_mul(a.tail, b))
+      return benda/big/mag_add(benda/big/mag_mul_digit(b, a.head, 0), rest, 0)
+",
+    // Long division, one limb at a time, from the most significant one.
+    "benda/big/mag_divmod(a, b):
+  match a:
+    case List/Nil:
+      return ([], [])
+    case List/Cons:
+      (q, r) = benda/big/mag_divmod(a.tail, b)
+      r = benda/big/cons(a.head, r)
+      d = benda/big/mag_digit(r, b, 0, 4095)
+      r = benda/big/mag_sub(r, benda/big/mag_mul_digit(b, d, 0), 0)
+      return (benda/big/cons(d, q), r)
+",
+    // The largest limb `d` between `lo` and `hi` with `b * d <= r`.
+    "benda/big/mag_digit(r, b, lo, hi):
+  if lo == hi:
+    return lo
+  else:
+    mid = (lo + hi + 1) / 2
+    if benda/big/mag_cmp(benda/big/mag_mul_digit(b, mid, 0), r) == 2:
+      return benda/big/mag_digit(r, b, lo, mid - 1)
+    else:
+      return benda/big/mag_digit(r, b, mid, hi)
+",
+    "benda/big/add(a, b):
+  sa = benda/big/head(a)
+  sb = benda/big/head(b)
+  if (sa == 2) | (sb == 2):
+    return [2]
+  else:
+    return benda/big/add_signed(sa, benda/big/tail(a), sb, benda/big/tail(b))
+",
+    "benda/big/add_signed(sa, ma, sb, mb):
+  if sa == sb:
+    return benda/big/make(sa, benda/big/mag_add(ma, mb, 0))
+  elif benda/big/mag_cmp(ma, mb) == 0:
+    return benda/big/make(sb, benda/big/mag_sub(mb, ma, 0))
+  else:
+    return benda/big/make(sa, benda/big/mag_sub(ma, mb, 0))
+",
+    "benda/big/sub(a, b):
+  sb = benda/big/head(b)
+  if sb == 2:
+    return [2]
+  else:
+    return benda/big/add(a, benda/big/make(1 - sb, benda/big/tail(b)))
+",
+    "benda/big/mul(a, b):
+  sa = benda/big/head(a)
+  sb = benda/big/head(b)
+  if (sa == 2) | (sb == 2):
+    return [2]
+  else:
+    return benda/big/make((sa + sb) % 2, benda/big/mag_mul(benda/big/tail(a), benda/big/tail(b)))
+",
+    // Like Python, the quotient is rounded down and the remainder has the
+    // sign of the divisor.
+    "benda/big/divmod(a, b):
+  sa = benda/big/head(a)
+  sb = benda/big/head(b)
+  mb = benda/big/tail(b)
+  if (sa == 2) | (sb == 2):
+    return ([2], [2])
+  else:
+    match mb:
+      case List/Nil:
+        return ([2], [2])
+      case List/Cons:
+        mb = List/Cons(mb.head, mb.tail)
+        (q, r) = benda/big/mag_divmod(benda/big/tail(a), mb)
+        if sa == sb:
+          return (benda/big/make(0, q), benda/big/make(sb, r))
+        else:
+          match r:
+            case List/Nil:
+              return (benda/big/make(1, q), [0])
+            case List/Cons:
+              q = benda/big/mag_add(q, [1], 0)
+              r = benda/big/mag_sub(mb, List/Cons(r.head, r.tail), 0)
+              return (benda/big/make(1, q), benda/big/make(sb, r))
+",
+    "benda/big/floordiv(a, b):
+  (q, *) = benda/big/divmod(a, b)
+  return q
+",
+    "benda/big/mod(a, b):
+  (*, r) = benda/big/divmod(a, b)
+  return r
+",
+    "benda/big/lt(a, b):
+  sa = benda/big/head(a)
+  sb = benda/big/head(b)
+  if sa == sb:
+    c = benda/big/mag_cmp(benda/big/tail(a), benda/big/tail(b))
+    if sa == 0:
+      return c == 0
+    else:
+      return c == 2
+  else:
+    return sa == 1
+",
+    "benda/big/eq(a, b):
+  if benda/big/head(a) == benda/big/head(b):
+    return benda/big/mag_cmp(benda/big/tail(a), benda/big/tail(b)) == 1
+  else:
+    return 0
+",
+];
+
+pub const FROM_U24: &str = "benda/big/from_u24";
+
+pub fn definitions() -> Vec<imp::Definition> {
+    DEFS.iter()
+        .map(|code| PyParser::new(code).parse_def(Indent::Val(0)).unwrap().0)
+        .collect()
+}
+
+// Writes an operation on bignums as a call to the library. Comparisons are
+// all written with `lt` and `eq`.
+pub fn operation(op: Op, lhs: Expr, rhs: Expr) -> PyResult<Expr> {
+    let fun = match op {
+        Op::ADD => "benda/big/add",
+        Op::SUB => "benda/big/sub",
+        Op::MUL => "benda/big/mul",
+        Op::DIV => "benda/big/floordiv",
+        Op::REM => "benda/big/mod",
+        Op::LT => "benda/big/lt",
+        Op::EQ => "benda/big/eq",
+        Op::GT => return Ok(call("benda/big/lt", vec![rhs, lhs])),
+        Op::NEQ => {
+            return Ok(Expr::Opr {
+                op: Op::EQ,
+                lhs: Box::new(call("benda/big/eq", vec![lhs, rhs])),
+                rhs: Box::new(Expr::Num {
+                    val: bend::fun::Num::U24(0),
+                }),
+            })
+        }
+        op => {
+            return Err(PyTypeError::new_err(format!(
+                "The operation '{}' is not supported on bignums",
+                op
+            )))
+        }
+    };
+
+    Ok(call(fun, vec![lhs, rhs]))
+}
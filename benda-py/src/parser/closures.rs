@@ -0,0 +1,172 @@
+use bend::{
+    fun::Name,
+    imp::{AssignPattern, Expr, Stmt},
+};
+use indexmap::IndexSet;
+use rustpython_parser::ast::{Expr as rExpr, Stmt as rStmt};
+
+use super::num_types::{expr_children, stmt_children, stmt_exprs};
+use crate::types::names::escape;
+
+// The local variables of a Python function: its parameters and every name it
+// assigns, anywhere in its body. Only these can be captured by a nested def.
+pub fn py_locals(params: &[Name], body: &[rStmt]) -> Vec<Name> {
+    let mut locals: IndexSet<Name> = params.iter().cloned().collect();
+    collect_py_stmts(body, &mut locals);
+    locals.into_iter().collect()
+}
+
+fn collect_py_stmts(stmts: &[rStmt], locals: &mut IndexSet<Name>) {
+    for stmt in stmts {
+        match stmt {
+            rStmt::Assign(assign) => {
+                for target in &assign.targets {
+                    collect_py_target(target, locals);
+                }
+            }
+            rStmt::AugAssign(assign) => collect_py_target(&assign.target, locals),
+            rStmt::AnnAssign(assign) => collect_py_target(&assign.target, locals),
+            rStmt::FunctionDef(fun_def) => {
+                locals.insert(Name::new(escape(&fun_def.name)));
+            }
+            rStmt::If(stmt_if) => {
+                collect_py_stmts(&stmt_if.body, locals);
+                collect_py_stmts(&stmt_if.orelse, locals);
+            }
+            rStmt::For(stmt_for) => {
+                collect_py_target(&stmt_for.target, locals);
+                collect_py_stmts(&stmt_for.body, locals);
+                collect_py_stmts(&stmt_for.orelse, locals);
+            }
+            rStmt::While(stmt_while) => {
+                collect_py_stmts(&stmt_while.body, locals);
+                collect_py_stmts(&stmt_while.orelse, locals);
+            }
+            rStmt::With(with) => collect_py_stmts(&with.body, locals),
+            rStmt::Match(stmt_match) => {
+                for case in &stmt_match.cases {
+                    collect_py_stmts(&case.body, locals);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_py_target(target: &rExpr, locals: &mut IndexSet<Name>) {
+    match target {
+        rExpr::Name(name) => {
+            locals.insert(Name::new(escape(&name.id)));
+        }
+        rExpr::Tuple(tup) => tup
+            .elts
+            .iter()
+            .for_each(|elt| collect_py_target(elt, locals)),
+        rExpr::List(lst) => lst
+            .elts
+            .iter()
+            .for_each(|elt| collect_py_target(elt, locals)),
+        rExpr::Starred(starred) => collect_py_target(&starred.value, locals),
+        _ => {}
+    }
+}
+
+// The variables used by a parsed body that it doesn't bind itself, in the
+// order they first appear.
+pub fn free_vars(params: &[Name], body: &Stmt) -> Vec<Name> {
+    let mut bound: IndexSet<Name> = params.iter().cloned().collect();
+    let mut used: IndexSet<Name> = IndexSet::new();
+    collect_stmt(body, &mut bound, &mut used);
+
+    used.into_iter()
+        .filter(|nam| !bound.contains(nam))
+        .collect()
+}
+
+fn collect_stmt(stmt: &Stmt, bound: &mut IndexSet<Name>, used: &mut IndexSet<Name>) {
+    match stmt {
+        Stmt::Assign { pat, .. } | Stmt::Ask { pat, .. } => bind_pattern(pat, bound),
+        Stmt::Match { bnd, .. } | Stmt::Switch { bnd, .. } | Stmt::Fold { bnd, .. } => {
+            bound.extend(bnd.clone());
+        }
+        Stmt::Bend { bnd, .. } => bound.extend(bnd.iter().flatten().cloned()),
+        Stmt::Use { nam, .. } => {
+            bound.insert(nam.clone());
+        }
+        Stmt::Open { var, .. } => {
+            used.insert(var.clone());
+        }
+        _ => {}
+    }
+
+    for expr in stmt_exprs(stmt) {
+        collect_expr(expr, bound, used);
+    }
+    for child in stmt_children(stmt) {
+        collect_stmt(child, bound, used);
+    }
+}
+
+fn bind_pattern(pat: &AssignPattern, bound: &mut IndexSet<Name>) {
+    match pat {
+        AssignPattern::Var(nam) | AssignPattern::Chn(nam) => {
+            bound.insert(nam.clone());
+        }
+        AssignPattern::Tup(pats) | AssignPattern::Sup(pats) => {
+            pats.iter().for_each(|pat| bind_pattern(pat, bound))
+        }
+        AssignPattern::Eraser | AssignPattern::MapSet(..) => {}
+    }
+}
+
+fn collect_expr(expr: &Expr, bound: &mut IndexSet<Name>, used: &mut IndexSet<Name>) {
+    match expr {
+        // The fields of a matched value, like `x.head`, are bound by the match.
+        Expr::Var { nam } if !nam.contains('.') => {
+            used.insert(nam.clone());
+        }
+        Expr::Lam { names, .. } => bound.extend(names.iter().map(|(nam, _)| nam.clone())),
+        Expr::LstMap { bind, .. } => {
+            bound.insert(bind.clone());
+        }
+        _ => {}
+    }
+
+    for child in expr_children(expr) {
+        collect_expr(child, bound, used);
+    }
+}
+
+// The fields read by the expressions of a statement, like `p.x`, without
+// the ones of its nested statements.
+pub fn field_vars(stmt: &Stmt) -> Vec<Name> {
+    let mut vars: IndexSet<Name> = IndexSet::new();
+    let mut exprs = stmt_exprs(stmt);
+
+    while let Some(expr) = exprs.pop() {
+        if let Expr::Var { nam } = expr {
+            if nam.contains('.') {
+                vars.insert(nam.clone());
+            }
+        }
+        exprs.extend(expr_children(expr));
+    }
+
+    vars.into_iter().collect()
+}
+
+// The names used by a value given to Bend, like the `@bjit` functions passed
+// as arguments. Values may be very deep, so this doesn't recurse.
+pub fn expr_names(expr: &Expr) -> Vec<Name> {
+    let mut names: IndexSet<Name> = IndexSet::new();
+    let mut exprs = vec![expr];
+
+    while let Some(expr) = exprs.pop() {
+        if let Expr::Var { nam } = expr {
+            names.insert(nam.clone());
+        }
+        exprs.extend(expr_children(expr));
+    }
+
+    names.into_iter().collect()
+}
@@ -1,15 +1,19 @@
-#![allow(clippy::cmp_owned)]
+#![allow(clippy::cmp_owned, clippy::mutable_key_type)]
 use core::panic;
-use std::vec;
+use std::{cell::RefCell, collections::HashMap, vec};
 
 use bend::{
-    fun::{self, Adt, Book, CtrField, Name, Op, Rule, STRINGS},
+    fun::{self, Adt, Book, CtrField, Name, Op, Rule, Term, STRINGS},
     imp::{self, Expr, MatchArm, Stmt},
 };
 use indexmap::IndexMap;
+use modules::Module;
+use num_types::{stmt_children, stmt_children_mut, NumType, NumTypes, Signature};
+use rustpython_ast::{text_size::TextRange, Fold};
 use rustpython_parser::ast::{
-    located, ExprAttribute, ExprBinOp, StmtAssign, StmtClassDef, StmtExpr, StmtFunctionDef, StmtIf,
-    StmtMatch,
+    located, ArgWithDefault, ExprAttribute, ExprBinOp, ExprContext, ExprName, ExprUnaryOp,
+    Identifier, StmtAnnAssign, StmtAssign, StmtAugAssign, StmtClassDef, StmtExpr, StmtFunctionDef,
+    StmtIf, StmtMatch, UnaryOp,
 };
 
 use rustpython_parser::ast::CmpOp as rCmpOp;
@@ -19,30 +23,39 @@ use rustpython_parser::ast::Pattern as rPattern;
 use rustpython_parser::ast::Stmt as rStmt;
 
 use crate::benda_ffi::run;
+use crate::types::{
+    bignum::literal,
+    class_adt, class_ctr, extract_type, fit_value,
+    i24::i24,
+    names::{escape, unescape},
+    overflow::{self, check_literal, Overflow},
+    readback::Hint,
+    u24::u24,
+    value_to_fun,
+};
+use num_bigint::{BigInt, Sign};
 use num_traits::cast::ToPrimitive;
+use pyo3::{
+    exceptions::{PyAttributeError, PySyntaxError, PyTypeError},
+    prelude::*,
+    types::PyModule,
+};
+
+mod annotations;
+mod bignum;
+mod closures;
+pub mod modules;
+mod num_ops;
+mod num_types;
+mod purity;
 
 #[derive(Clone, Debug)]
 enum FromExpr {
-    CtrField(Vec<CtrField>),
     Expr(imp::Expr),
     Statement(imp::Stmt),
 }
 
 impl FromExpr {
-    pub fn to_expr(&self) -> Option<Expr> {
-        if let FromExpr::Expr(exp) = self {
-            return Some(exp.clone());
-        }
-        None
-    }
-
-    pub fn to_stmt(&self) -> Option<Stmt> {
-        if let FromExpr::Statement(stmt) = self {
-            return Some(stmt.clone());
-        }
-        None
-    }
-
     pub fn get_var_name(&self) -> Option<Name> {
         if let FromExpr::Expr(Expr::Var { nam }) = self {
             Some(nam.clone())
@@ -55,7 +68,6 @@ impl FromExpr {
 #[derive(PartialEq)]
 enum CurContext {
     Match,
-    Main,
 }
 
 struct Context {
@@ -65,23 +77,101 @@ struct Context {
 }
 
 pub struct Parser {
-    statements: Vec<rStmt>,
+    modules: Vec<Module>,
+    // The module of the statements being parsed.
+    module: usize,
     book: Book,
     definitions: Vec<imp::Definition>,
     ctx: Option<Context>,
-    index: usize,
     fun_args: Vec<(String, imp::Expr)>,
+    user_adts: Vec<(String, Vec<(String, bool)>)>,
+    signatures: HashMap<Name, Signature>,
+    field_types: HashMap<Name, Vec<(Name, Option<NumType>)>>,
+    bignum: bool,
+    overflow: Overflow,
+    // The functions being parsed, from the outermost one, with the name of
+    // their Bend definition and their local variables.
+    scopes: Vec<(Name, Vec<Name>)>,
+    // The datatypes of the variables annotated with a class, like `p: Point`.
+    var_types: HashMap<Name, Name>,
+    // The parameters of the functions of the modules, with their default values.
+    fun_params: HashMap<Name, Vec<(Name, Option<rExpr>)>>,
+    // The methods of the classes, like `Circle/area` for `Circle`.
+    methods: HashMap<Name, Vec<Name>>,
+    // The methods called without the object, `@staticmethod`s and
+    // `@classmethod`s.
+    static_methods: Vec<Name>,
+    constants: Vec<(String, imp::Expr)>,
+    // The unions of the modules, like `Shape = Circle | Square`, with the
+    // names of their members.
+    unions: Vec<(Name, Vec<Name>)>,
+    // The fields of the classes, before they are put in their datatypes.
+    classes: IndexMap<Name, Vec<CtrField>>,
+    // The constructors of the unions, with the class each one is built from,
+    // like `Circle` for `Shape/Circle`.
+    ctr_aliases: HashMap<Name, Name>,
+    // The classes and unions annotated as `Optional` somewhere, whose
+    // datatypes get a `None` constructor, like `Node/None`.
+    optional_classes: Vec<Name>,
+    // The class of each field of the classes and of each parameter of the
+    // functions, when they are annotated with one, so that the values given
+    // to them are built with the constructors of its datatype.
+    field_classes: HashMap<Name, Vec<(Name, Option<Name>)>>,
+    param_classes: HashMap<Name, Vec<Option<Name>>>,
+    // The class returned by each function, when it is annotated with one.
+    return_classes: HashMap<Name, Name>,
+    // The datatypes of the fields of each constructor.
+    field_adts: HashMap<Name, Vec<Option<Name>>>,
+    // The first error of the Python code that is not a bug of the parser,
+    // like a literal that doesn't fit, given back by `parse`.
+    error: RefCell<Option<PyErr>>,
 }
 
 impl Parser {
-    pub fn new(statements: Vec<rStmt>, index: usize, fun_args: Vec<(String, imp::Expr)>) -> Self {
+    pub fn new(
+        modules: Vec<Module>,
+        fun_args: Vec<(String, imp::Expr)>,
+        user_adts: Vec<(String, Vec<(String, bool)>)>,
+        bignum: bool,
+        overflow: Overflow,
+    ) -> Self {
         Self {
-            statements,
+            modules,
+            module: 0,
             book: bend::fun::Book::builtins(),
             definitions: vec![],
-            index,
             ctx: None,
-            fun_args,
+            fun_args: fun_args
+                .into_iter()
+                .map(|(name, expr)| (escape(&name), expr))
+                .collect(),
+            user_adts,
+            signatures: HashMap::new(),
+            field_types: HashMap::new(),
+            bignum,
+            overflow,
+            scopes: vec![],
+            var_types: HashMap::new(),
+            fun_params: HashMap::new(),
+            methods: HashMap::new(),
+            static_methods: vec![],
+            constants: vec![],
+            unions: vec![],
+            classes: IndexMap::new(),
+            ctr_aliases: HashMap::new(),
+            optional_classes: vec![],
+            field_classes: HashMap::new(),
+            param_classes: HashMap::new(),
+            return_classes: HashMap::new(),
+            field_adts: HashMap::new(),
+            error: RefCell::new(None),
+        }
+    }
+
+    // Keeps the first error, the parsing goes on without it.
+    fn report(&self, result: PyResult<()>) {
+        if let Err(err) = result {
+            self.error.borrow_mut().get_or_insert(err);
         }
     }
 
@@ -104,10 +194,43 @@ impl Parser {
     fn parse_expr_type(&self, expr: rExpr) -> Option<FromExpr> {
         match expr {
             rExpr::Attribute(att) => {
-                if let Some(switch) = self.parse_switch_expr(att) {
+                if let Some(switch) = self.parse_switch_expr(att.clone()) {
                     return Some(switch);
                 }
-                None
+
+                // `shapes.area` is the function `area` of the module `shapes`.
+                if let Some(module) = self.imported_module(&att.value) {
+                    return Some(FromExpr::Expr(Expr::Var {
+                        nam: self.global_name(module, att.attr.as_str()),
+                    }));
+                }
+
+                if let Some(ctr) = self.enum_member(&att) {
+                    return Some(FromExpr::Expr(Expr::Ctr {
+                        name: ctr,
+                        args: vec![],
+                        kwargs: vec![],
+                    }));
+                }
+
+                if let Some(method) = self.class_method(&att) {
+                    return Some(FromExpr::Expr(Expr::Var { nam: method }));
+                }
+
+                // `p.x` is the variable Bend binds for the field when `p` is
+                // opened, which is done later by `open_fields`.
+                match self.parse_expr_type(*att.value)? {
+                    FromExpr::Expr(Expr::Var { nam }) => Some(FromExpr::Expr(Expr::Var {
+                        nam: Name::new(format!("{}.{}", nam, escape(&att.attr))),
+                    })),
+                    _ => {
+                        self.report(Err(PyTypeError::new_err(format!(
+                            "The field '{}' can only be read from a variable",
+                            att.attr
+                        ))));
+                        Some(FromExpr::Expr(Expr::Era))
+                    }
+                }
             }
             rExpr::Compare(comp) => {
                 let left = self.parse_expr_type(*comp.left).unwrap();
@@ -115,140 +238,308 @@ impl Parser {
                     .parse_expr_type(comp.comparators.first().unwrap().clone())
                     .unwrap();
 
-                let op = match comp.ops.first().unwrap() {
-                    rCmpOp::Eq => Op::EQ,
-                    rCmpOp::NotEq => Op::NEQ,
-                    rCmpOp::Lt => Op::LT,
-                    rCmpOp::LtE => todo!(),
-                    rCmpOp::Gt => Op::GT,
-                    rCmpOp::GtE => todo!(),
-                    rCmpOp::Is => todo!(),
-                    rCmpOp::IsNot => todo!(),
+                // HVM has no `<=` and `>=`, so they are written like
+                // `not a > b` and `not a < b`.
+                let (op, negated) = match comp.ops.first().unwrap() {
+                    rCmpOp::Is | rCmpOp::IsNot => {
+                        let negated = comp.ops[0] == rCmpOp::IsNot;
+                        return Some(FromExpr::Expr(self.is_none(left, right, negated)));
+                    }
+                    rCmpOp::Eq => (Op::EQ, false),
+                    rCmpOp::NotEq => (Op::NEQ, false),
+                    rCmpOp::Lt => (Op::LT, false),
+                    rCmpOp::LtE => (Op::GT, true),
+                    rCmpOp::Gt => (Op::GT, false),
+                    rCmpOp::GtE => (Op::LT, true),
                     rCmpOp::In => todo!(),
                     rCmpOp::NotIn => todo!(),
                 };
 
                 if let (FromExpr::Expr(left), FromExpr::Expr(right)) = (left, right) {
-                    return Some(FromExpr::Expr(Expr::Opr {
+                    let cmp = Expr::Opr {
                         op,
                         lhs: Box::new(left),
                         rhs: Box::new(right),
-                    }));
+                    };
+                    if negated {
+                        return Some(FromExpr::Expr(Expr::Opr {
+                            op: Op::EQ,
+                            lhs: Box::new(cmp),
+                            rhs: Box::new(Expr::Num {
+                                val: fun::Num::U24(0),
+                            }),
+                        }));
+                    }
+                    return Some(FromExpr::Expr(cmp));
                 }
                 None
             }
             rExpr::BinOp(bin_op) => self.parse_bin_op(bin_op),
-            rExpr::Constant(c) => match c.value {
-                located::Constant::None => todo!(),
-                located::Constant::Bool(_) => todo!(),
-                located::Constant::Str(str) => {
-                    let nam = Name::new(str.clone());
-                    let adt = self.book.adts.get(&nam);
-
-                    if let Some(_adt) = adt {
-                        return Some(FromExpr::Expr(imp::Expr::Var { nam }));
+            rExpr::UnaryOp(unary_op) => self.parse_unary_op(unary_op),
+            rExpr::Constant(c) => self.parse_constant(c.value),
+            rExpr::Tuple(tup) => {
+                let mut els: Vec<Expr> = vec![];
+
+                for (index, el) in tup.elts.into_iter().enumerate() {
+                    match self.parse_expr_type(el) {
+                        Some(FromExpr::Expr(e)) => els.push(e),
+                        _ => {
+                            self.report(Err(PySyntaxError::new_err(format!(
+                                "Could not parse the element {} of the tuple",
+                                index
+                            ))));
+                            els.push(Expr::Era);
+                        }
                     }
-                    Some(FromExpr::Expr(Expr::Str {
-                        val: STRINGS.get(str.as_str()),
-                    }))
-                }
-                located::Constant::Bytes(_) => todo!(),
-                located::Constant::Int(val) => Some(FromExpr::Expr(imp::Expr::Num {
-                    val: bend::fun::Num::U24(val.to_u32().unwrap()),
-                })),
-                located::Constant::Tuple(_) => todo!(),
-                located::Constant::Float(val) => Some(FromExpr::Expr(imp::Expr::Num {
-                    val: bend::fun::Num::F24(val.to_f32().unwrap()),
-                })),
-                located::Constant::Complex { real: _, imag: _ } => todo!(),
-                located::Constant::Ellipsis => todo!(),
-            },
+                }
+
+                Some(FromExpr::Expr(Expr::Tup { els }))
+            }
 
             rExpr::Name(n) => {
-                let mut name = n.id.to_string();
+                let mut name = escape(&n.id);
 
                 if let Some(ctx) = &self.ctx {
                     if ctx.now == CurContext::Match {
                         for var in &ctx.vars {
                             if *var == n.id.to_string() {
-                                name = format!("{}.{}", ctx.subs.first().unwrap(), var);
+                                name = format!("{}.{}", ctx.subs.first().unwrap(), escape(var));
                             }
                         }
                     }
                 }
 
+                if name == escape(&n.id) && !self.is_local(&n.id) {
+                    if let Some(global) = self.resolve(&n.id) {
+                        name = global.to_string();
+                    }
+                }
+
                 Some(FromExpr::Expr(imp::Expr::Var {
                     nam: Name::new(name),
                 }))
             }
 
             rExpr::Call(c) => {
-                let fun = c.func;
+                let mut args: Vec<Expr> = vec![];
+
+                // `c.area(2)` calls the method of the class of `c`, given `c` first.
+                let expr = match c.func.as_ref() {
+                    rExpr::Attribute(att) => match self.bound_method(att) {
+                        Some((method, value)) => {
+                            args.extend(value);
+                            Some(FromExpr::Expr(Expr::Var { nam: method }))
+                        }
+                        None => self.parse_expr_type(*c.func),
+                    },
+                    _ => self.parse_expr_type(*c.func),
+                };
 
-                let expr = self.parse_expr_type(*fun);
+                // Anything evaluating to a function can be called, like a
+                // parameter, a lambda or the result of another call.
+                if let Some(FromExpr::Expr(fun)) = expr {
+                    for (index, arg) in c.args.into_iter().enumerate() {
+                        match self.parse_expr_type(arg) {
+                            Some(FromExpr::Expr(e)) => args.push(e),
+                            _ => {
+                                self.report(Err(PySyntaxError::new_err(format!(
+                                    "Could not parse the argument {} of the call",
+                                    index
+                                ))));
+                                args.push(Expr::Era);
+                            }
+                        }
+                    }
 
-                if let Some(FromExpr::Expr(Expr::Var { ref nam })) = expr {
-                    let mut args: Vec<Expr> = vec![];
+                    let mut kwargs: Vec<(Name, Expr)> = vec![];
 
-                    for arg in c.args {
-                        let arg = self.parse_expr_type(arg);
+                    for keyword in c.keywords {
+                        let Some(arg) = keyword.arg else {
+                            self.report(Err(PyTypeError::new_err(
+                                "Keyword arguments can't be unpacked with '**'",
+                            )));
+                            continue;
+                        };
 
-                        if let Some(FromExpr::Expr(e)) = arg {
-                            args.push(e);
+                        match self.parse_expr_type(keyword.value) {
+                            Some(FromExpr::Expr(e)) => kwargs.push((Name::new(escape(&arg)), e)),
+                            _ => self.report(Err(PySyntaxError::new_err(format!(
+                                "Could not parse the argument '{}' of the call",
+                                arg
+                            )))),
                         }
                     }
 
-                    if let Some(val) = self.find_in_ctrs(nam) {
-                        return Some(FromExpr::Expr(imp::Expr::Ctr {
-                            name: val.clone(),
-                            args,
-                            kwargs: vec![],
-                        }));
+                    if let Expr::Var { nam } = &fun {
+                        // Constructors take their fields by name.
+                        if let Some(val) = self.find_in_ctrs(nam) {
+                            self.fit_fields(&val, &mut args, &mut kwargs);
+                            return Some(FromExpr::Expr(imp::Expr::Ctr {
+                                name: val.clone(),
+                                args,
+                                kwargs,
+                            }));
+                        }
+
+                        if let Some(params) = self.fun_params.get(nam) {
+                            args = self.bind_args(nam, params, args, std::mem::take(&mut kwargs));
+                            let classes = &self.param_classes[nam];
+                            for (arg, class) in args.iter_mut().zip(classes) {
+                                self.fit(arg, class.as_ref());
+                            }
+                        }
+                    }
+
+                    if let Some((kwarg, _)) = kwargs.first() {
+                        self.report(Err(PyTypeError::new_err(format!(
+                            "The keyword argument '{}' can only be given to constructors and functions of the module",
+                            unescape(kwarg)
+                        ))));
                     }
+
                     return Some(FromExpr::Expr(imp::Expr::Call {
-                        fun: Box::new(Expr::Var {
-                            nam: Name::new(nam.to_string()),
-                        }),
+                        fun: Box::new(fun),
                         args,
                         kwargs: vec![],
                     }));
                 }
                 expr
             }
+            rExpr::Lambda(lambda) => {
+                let names = lambda
+                    .args
+                    .args
+                    .iter()
+                    .map(|arg| (Name::new(escape(&arg.def.arg)), false))
+                    .collect();
+
+                match self.parse_expr_type(*lambda.body)? {
+                    FromExpr::Expr(bod) => Some(FromExpr::Expr(imp::Expr::Lam {
+                        names,
+                        bod: Box::new(bod),
+                    })),
+                    _ => None,
+                }
+            }
             _ => todo!(),
         }
     }
 
-    fn parse_adt_create(&self, left: &FromExpr, right: &FromExpr) -> Option<FromExpr> {
-        if let (Some(nam_l), Some(nam_r)) = (left.get_var_name(), right.get_var_name()) {
-            let adt_l = self.book.adts.get(&nam_l);
-            let adt_r = self.book.adts.get(&nam_r);
+    // Bend functions only take positional arguments, so the keyword
+    // arguments are put in place of their parameters, and the missing ones
+    // take their default values, like Python does.
+    fn bind_args(
+        &self,
+        fun: &Name,
+        params: &[(Name, Option<rExpr>)],
+        args: Vec<Expr>,
+        kwargs: Vec<(Name, Expr)>,
+    ) -> Vec<Expr> {
+        if args.len() > params.len() {
+            self.report(Err(PyTypeError::new_err(format!(
+                "{}() takes {} positional arguments but {} were given",
+                unescape(fun),
+                params.len(),
+                args.len()
+            ))));
+        }
 
-            let mut is_adt = false;
+        let mut bound: Vec<Option<Expr>> = args.into_iter().map(Some).collect();
+        if bound.len() < params.len() {
+            bound.resize(params.len(), None);
+        }
 
-            match (adt_l, adt_r) {
-                (None, None) => {}
-                (_, _) => {
-                    is_adt = true;
-                }
+        for (name, val) in kwargs {
+            let Some(index) = params.iter().position(|(param, _)| *param == name) else {
+                self.report(Err(PyTypeError::new_err(format!(
+                    "{}() got an unexpected keyword argument '{}'",
+                    unescape(fun),
+                    unescape(&name)
+                ))));
+                continue;
+            };
+            if bound[index].is_some() {
+                self.report(Err(PyTypeError::new_err(format!(
+                    "{}() got multiple values for argument '{}'",
+                    unescape(fun),
+                    unescape(&name)
+                ))));
             }
+            bound[index] = Some(val);
+        }
 
-            let mut fields: Vec<CtrField> = vec![];
+        bound
+            .into_iter()
+            .zip(params)
+            .map(|(arg, (param, default))| match (arg, default) {
+                (Some(arg), _) => arg,
+                (None, Some(default)) => match self.parse_expr_type(default.clone()) {
+                    Some(FromExpr::Expr(default)) => default,
+                    _ => {
+                        self.report(Err(PySyntaxError::new_err(format!(
+                            "Could not parse the default value of '{}'",
+                            unescape(param)
+                        ))));
+                        Expr::Era
+                    }
+                },
+                (None, None) => {
+                    self.report(Err(PyTypeError::new_err(format!(
+                        "{}() missing required argument '{}'",
+                        unescape(fun),
+                        unescape(param)
+                    ))));
+                    Expr::Era
+                }
+            })
+            .collect()
+    }
 
-            if is_adt {
-                fields.push(CtrField {
-                    nam: nam_l.clone(),
-                    rec: false,
-                });
-                fields.push(CtrField {
-                    nam: nam_r.clone(),
-                    rec: false,
-                });
-                return Some(FromExpr::CtrField(fields));
+    fn parse_constant(&self, constant: located::Constant) -> Option<FromExpr> {
+        match constant {
+            // An empty field, like the end of a linked list, is erased.
+            located::Constant::None => Some(FromExpr::Expr(Expr::Era)),
+            located::Constant::Bool(_) => todo!(),
+            located::Constant::Str(str) => {
+                let nam = Name::new(str.clone());
+                let adt = self.book.adts.get(&nam);
+
+                if let Some(_adt) = adt {
+                    return Some(FromExpr::Expr(imp::Expr::Var { nam }));
+                }
+                Some(FromExpr::Expr(Expr::Str {
+                    val: STRINGS.get(str.as_str()),
+                }))
             }
-        }
+            located::Constant::Bytes(_) => todo!(),
+            located::Constant::Int(val) => Some(FromExpr::Expr(
+                self.parse_int(val.to_string().parse().unwrap()),
+            )),
+            // Tuples of constants, like `(1, 2)`, are folded into a constant.
+            located::Constant::Tuple(vals) => {
+                let mut els: Vec<Expr> = vec![];
+
+                for (index, val) in vals.into_iter().enumerate() {
+                    match self.parse_constant(val) {
+                        Some(FromExpr::Expr(e)) => els.push(e),
+                        _ => {
+                            self.report(Err(PySyntaxError::new_err(format!(
+                                "Could not parse the element {} of the tuple",
+                                index
+                            ))));
+                            els.push(Expr::Era);
+                        }
+                    }
+                }
 
-        None
+                Some(FromExpr::Expr(Expr::Tup { els }))
+            }
+            located::Constant::Float(val) => Some(FromExpr::Expr(imp::Expr::Num {
+                val: bend::fun::Num::F24(val.to_f32().unwrap()),
+            })),
+            located::Constant::Complex { real: _, imag: _ } => todo!(),
+            located::Constant::Ellipsis => todo!(),
+        }
     }
 
     fn parse_bin_op(&self, bin: ExprBinOp) -> Option<FromExpr> {
@@ -257,36 +548,122 @@ impl Parser {
         let left: FromExpr = self.parse_expr_type(*bin.left).unwrap();
         let right: FromExpr = self.parse_expr_type(*bin.right).unwrap();
 
-        let op: Op = match bin.op {
+        if let (FromExpr::Expr(left), FromExpr::Expr(right)) = (left, right) {
+            return Some(FromExpr::Expr(Self::operation(&bin.op, left, right)));
+        }
+        todo!()
+    }
+
+    // Python's floor division and modulo round down, unlike the ones of HVM,
+    // so they are written as calls that get the function for the type of
+    // their operands once the numeric types are inferred.
+    fn operation(op: &rOperator, lhs: Expr, rhs: Expr) -> Expr {
+        match op {
+            rOperator::FloorDiv => return num_types::call(num_ops::FLOOR_DIV, vec![lhs, rhs]),
+            rOperator::Mod => return num_types::call(num_ops::MOD, vec![lhs, rhs]),
+            _ => {}
+        }
+
+        Expr::Opr {
+            op: Self::parse_operator(op),
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+
+    fn parse_operator(op: &rOperator) -> Op {
+        match op {
             rOperator::Add => Op::ADD,
             rOperator::Sub => Op::SUB,
             rOperator::Mult => Op::MUL,
             rOperator::MatMult => todo!(),
             rOperator::Div => Op::DIV,
-            rOperator::Mod => todo!(),
             rOperator::Pow => Op::POW,
             rOperator::LShift => Op::SHL,
             rOperator::RShift => Op::SHR,
             rOperator::BitOr => Op::OR,
             rOperator::BitXor => Op::XOR,
             rOperator::BitAnd => Op::AND,
-            rOperator::FloorDiv => todo!(),
+            rOperator::FloorDiv | rOperator::Mod => unreachable!(),
+        }
+    }
+
+    // Int literals are u24, or i24 when negative. The ones that don't fit in
+    // 24 bits are bignums, when enabled, or follow the overflow policy.
+    fn parse_int(&self, val: BigInt) -> imp::Expr {
+        let fits = match val.to_i64() {
+            Some(val) => (i24::MIN..=u24::MAX as i64).contains(&val),
+            None => false,
         };
 
-        if let Some(adt_op) = self.parse_adt_create(&left, &right) {
-            return Some(adt_op);
+        if !fits && self.bignum {
+            return literal(&val);
         }
 
-        if let (FromExpr::Expr(left), FromExpr::Expr(right)) = (left, right) {
-            let operation = imp::Expr::Opr {
-                op,
-                lhs: Box::new(left),
-                rhs: Box::new(right),
-            };
+        if !fits {
+            self.report(check_literal(self.overflow, &val.to_string()));
+        }
+
+        let bits = (&val & BigInt::from(u24::MAX)).to_u32().unwrap();
 
-            return Some(FromExpr::Expr(operation));
+        if val.sign() == Sign::Minus {
+            imp::Expr::Num {
+                val: bend::fun::Num::I24(i24::new(bits as i32).get()),
+            }
+        } else {
+            imp::Expr::Num {
+                val: bend::fun::Num::U24(bits),
+            }
         }
-        todo!()
+    }
+
+    // Negative literals become signed numbers, other unary operations are
+    // written with the binary operations of Bend.
+    fn parse_unary_op(&self, unary: ExprUnaryOp) -> Option<FromExpr> {
+        if let (UnaryOp::USub, rExpr::Constant(c)) = (unary.op, unary.operand.as_ref()) {
+            match &c.value {
+                located::Constant::Int(val) => {
+                    let val: BigInt = val.to_string().parse().unwrap();
+                    return Some(FromExpr::Expr(self.parse_int(-val)));
+                }
+                located::Constant::Float(val) => {
+                    return Some(FromExpr::Expr(imp::Expr::Num {
+                        val: bend::fun::Num::F24(-val.to_f32().unwrap()),
+                    }));
+                }
+                _ => {}
+            }
+        }
+
+        let operand = match self.parse_expr_type(*unary.operand)? {
+            FromExpr::Expr(operand) => operand,
+            _ => return None,
+        };
+
+        let zero = || imp::Expr::Num {
+            val: bend::fun::Num::U24(0),
+        };
+        let opr = |op, lhs, rhs| imp::Expr::Opr {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+
+        let expr = match unary.op {
+            UnaryOp::UAdd => operand,
+            UnaryOp::USub => opr(Op::SUB, zero(), operand),
+            UnaryOp::Not => opr(Op::EQ, operand, zero()),
+            // In two's complement, `~x` is `-x - 1`.
+            UnaryOp::Invert => opr(
+                Op::SUB,
+                opr(Op::SUB, zero(), operand),
+                imp::Expr::Num {
+                    val: bend::fun::Num::U24(1),
+                },
+            ),
+        };
+
+        Some(FromExpr::Expr(expr))
     }
 
     fn parse_assign(&mut self, assign: &StmtAssign) -> Option<FromExpr> {
@@ -299,15 +676,35 @@ impl Parser {
         stmts: &Vec<rStmt>,
         index: &usize,
     ) -> Option<imp::Stmt> {
+        if m.cases
+            .iter()
+            .any(|case| matches!(case.pattern, rPattern::MatchSequence(_)))
+        {
+            return self.parse_tuple_match(m, stmts, *index);
+        }
+
         let mut arms: Vec<imp::MatchArm> = vec![];
         let mut patt: Vec<String> = vec![];
+        let adt = self.match_adt(m);
+        let none = adt
+            .as_ref()
+            .map(|adt| Name::new(format!("{}/None", adt)))
+            .filter(|none| self.book.ctrs.contains_key(none));
 
         for case in &m.cases {
+            // `case _:` matches the constructors without a case of their own.
+            let wildcard = matches!(
+                &case.pattern,
+                rPattern::MatchAs(match_as) if match_as.pattern.is_none() && match_as.name.is_none()
+            );
+
             let pat = match &case.pattern {
                 rPattern::MatchValue(val) => {
                     let expr = self.parse_expr_type(*val.value.clone()).unwrap();
                     match expr {
                         FromExpr::Expr(imp::Expr::Var { nam }) => Some(nam),
+                        // Enum members, like `Color.RED`.
+                        FromExpr::Expr(imp::Expr::Ctr { name, .. }) => Some(name),
                         _ => None,
                     }
                 }
@@ -328,16 +725,38 @@ impl Parser {
                         _ => None,
                     }
                 }
+                rPattern::MatchSingleton(single) if single.value.is_none() => match &none {
+                    Some(none) => Some(none.clone()),
+                    None => {
+                        self.report(Err(PyTypeError::new_err(
+                            "The subject of the match can't be None, annotate it with 'Optional'",
+                        )));
+                        None
+                    }
+                },
                 rPattern::MatchSingleton(_) => todo!(),
                 rPattern::MatchSequence(_) => todo!(),
                 rPattern::MatchMapping(_) => todo!(),
                 rPattern::MatchStar(_) => todo!(),
+                rPattern::MatchAs(_) if wildcard => None,
                 rPattern::MatchAs(_) => todo!(),
                 rPattern::MatchOr(_) => todo!(),
             };
 
             let sub = self.parse_expr_type(*m.subject.clone());
 
+            // In a case of a class, the subject is of that class, so its
+            // methods can be called, like `s.area()` in `case Circle(radius)`.
+            let narrowed = match (&sub, &case.pattern, &pat) {
+                (Some(FromExpr::Expr(Expr::Var { nam })), rPattern::MatchClass(_), Some(class))
+                    if self.methods.contains_key(class) =>
+                {
+                    let old = self.var_types.insert(nam.clone(), class.clone());
+                    Some((nam.clone(), old))
+                }
+                _ => None,
+            };
+
             if let Some(FromExpr::Expr(Expr::Var { nam })) = sub {
                 self.ctx = Some(Context {
                     now: CurContext::Match,
@@ -348,12 +767,22 @@ impl Parser {
 
             let stmt_arm = self.parse_vec(&case.body.clone(), 0);
 
-            if let Some(pat) = pat {
-                let first = self.find_in_ctrs(&pat);
-                if let Some(FromExpr::Statement(a)) = stmt_arm {
-                    let arm = MatchArm { lft: first, rgt: a };
-                    arms.push(arm);
+            match narrowed {
+                Some((nam, Some(old))) => _ = self.var_types.insert(nam, old),
+                Some((nam, None)) => _ = self.var_types.remove(&nam),
+                None => {}
+            }
+
+            let lft = match (pat, &adt) {
+                (Some(pat), Some(adt)) if matches!(case.pattern, rPattern::MatchClass(_)) => {
+                    Some(self.adt_ctr(adt, &pat).or_else(|| self.find_in_ctrs(&pat)))
                 }
+                (Some(pat), _) => Some(self.find_in_ctrs(&pat)),
+                (None, _) if wildcard => Some(None),
+                (None, _) => None,
+            };
+            if let (Some(lft), Some(FromExpr::Statement(a))) = (lft, stmt_arm) {
+                arms.push(MatchArm { lft, rgt: a });
             }
         }
 
@@ -386,6 +815,127 @@ impl Parser {
         None
     }
 
+    // A match on a tuple tries its cases in order, destructuring the subject
+    // for each one. The numbers in a pattern, like the `0` in `case (0, x):`,
+    // are compared with the elements and, if one is not equal, the next case
+    // is tried. The case found is followed by the statements after the match.
+    fn parse_tuple_match(
+        &mut self,
+        m: &StmtMatch,
+        stmts: &[rStmt],
+        index: usize,
+    ) -> Option<imp::Stmt> {
+        let Some(FromExpr::Expr(subj)) = self.parse_expr_type(*m.subject.clone()) else {
+            return None;
+        };
+        // Escaped names never have a dash followed by a word, so these
+        // names don't clash with the ones of the function.
+        let subject = Name::new("tuple-subject");
+        let rest = &stmts[index + 1..];
+        let parse_body = |parser: &mut Self, body: Vec<rStmt>| match parser.parse_vec(&body, 0) {
+            Some(FromExpr::Statement(stmt)) => stmt,
+            _ => Stmt::Return {
+                term: Box::new(Expr::Era),
+            },
+        };
+
+        let mut cases: Vec<(imp::AssignPattern, Vec<Expr>, Stmt)> = vec![];
+        for (number, case) in m.cases.iter().enumerate() {
+            if case.guard.is_some() {
+                self.report(Err(PySyntaxError::new_err(
+                    "The cases of a match on a tuple can't have a guard",
+                )));
+                continue;
+            }
+
+            let mut conds: Vec<Expr> = vec![];
+            let Some(pat) = self.tuple_case_pattern(&case.pattern, number, &mut conds) else {
+                continue;
+            };
+            let body = case.body.iter().chain(rest).cloned().collect();
+            let body = parse_body(self, body);
+            cases.push((pat, conds, body));
+        }
+
+        // Without a case that matches, the match does nothing.
+        let mut lowered = parse_body(self, rest.to_vec());
+        for (pat, conds, body) in cases.into_iter().rev() {
+            let cond = conds.into_iter().reduce(|lhs, rhs| Expr::Opr {
+                op: Op::AND,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            });
+            let matched = match cond {
+                Some(cond) => Stmt::If {
+                    cond: Box::new(cond),
+                    then: Box::new(body),
+                    otherwise: Box::new(lowered),
+                    nxt: None,
+                },
+                None => body,
+            };
+            lowered = Stmt::Assign {
+                pat,
+                val: Box::new(Expr::Var {
+                    nam: subject.clone(),
+                }),
+                nxt: Some(Box::new(matched)),
+            };
+        }
+
+        Some(Stmt::Assign {
+            pat: imp::AssignPattern::Var(subject),
+            val: Box::new(subj),
+            nxt: Some(Box::new(lowered)),
+        })
+    }
+
+    // The names a pattern of a match on a tuple binds, with the numbers it
+    // has bound to other names and compared with them in `conds`.
+    fn tuple_case_pattern(
+        &mut self,
+        pattern: &rPattern,
+        case: usize,
+        conds: &mut Vec<Expr>,
+    ) -> Option<imp::AssignPattern> {
+        match pattern {
+            rPattern::MatchAs(match_as) if match_as.pattern.is_none() => match &match_as.name {
+                Some(name) => Some(imp::AssignPattern::Var(Name::new(escape(name)))),
+                None => Some(imp::AssignPattern::Eraser),
+            },
+            rPattern::MatchSequence(seq) => {
+                let mut els = vec![];
+                for pattern in &seq.patterns {
+                    els.push(self.tuple_case_pattern(pattern, case, conds)?);
+                }
+                Some(imp::AssignPattern::Tup(els))
+            }
+            rPattern::MatchValue(val) => match self.parse_expr_type(*val.value.clone()) {
+                Some(FromExpr::Expr(num @ Expr::Num { .. })) => {
+                    let var = Name::new(format!("tuple-{}-{}", case, conds.len()));
+                    conds.push(Expr::Opr {
+                        op: Op::EQ,
+                        lhs: Box::new(Expr::Var { nam: var.clone() }),
+                        rhs: Box::new(num),
+                    });
+                    Some(imp::AssignPattern::Var(var))
+                }
+                _ => {
+                    self.report(Err(PySyntaxError::new_err(
+                        "Only numbers can be compared with the elements of a tuple in a match",
+                    )));
+                    None
+                }
+            },
+            _ => {
+                self.report(Err(PySyntaxError::new_err(
+                    "The patterns of a match on a tuple can only have names, numbers and other tuples",
+                )));
+                None
+            }
+        }
+    }
+
     fn parse_switch(
         &mut self,
         name: &String,
@@ -423,7 +973,76 @@ impl Parser {
         None
     }
 
+    // Whether a name is a variable of the functions being parsed, which
+    // hides the globals of the module with the same name.
+    fn is_local(&self, name: &str) -> bool {
+        let name = Name::new(escape(name));
+        self.scopes.iter().any(|(_, locals)| locals.contains(&name))
+    }
+
+    // The Bend name of a global of the module being parsed, if the module
+    // defines or imports it.
+    fn resolve(&self, name: &str) -> Option<Name> {
+        let module = &self.modules[self.module];
+        match module.imports.get(name) {
+            Some((_, None)) => None,
+            Some(_) => Some(self.global_name(self.module, name)),
+            None if module.defined.contains(name) => Some(self.global_name(self.module, name)),
+            None => None,
+        }
+    }
+
+    // The Bend name of a global of a module, following the names it imports
+    // from the other modules of the package to the module defining them.
+    fn global_name(&self, module: usize, name: &str) -> Name {
+        let mut module = module;
+        let mut name = name.to_string();
+
+        // Bounded, since modules may import each other.
+        for _ in 0..self.modules.len() {
+            match self.modules[module].imports.get(&name) {
+                Some((from, Some(original))) => {
+                    module = *from;
+                    name = original.clone();
+                }
+                _ => break,
+            }
+        }
+
+        Name::new(format!("{}{}", self.modules[module].prefix, escape(&name)))
+    }
+
+    // The module an expression refers to, like `shapes` after `import shapes`
+    // or `pkg.shapes` after `import pkg.shapes`.
+    fn imported_module(&self, expr: &rExpr) -> Option<usize> {
+        let path = Self::dotted_name(expr)?;
+        if self.is_local(path.split('.').next()?) {
+            return None;
+        }
+
+        match self.modules[self.module].imports.get(&path) {
+            Some((module, None)) => Some(*module),
+            _ => None,
+        }
+    }
+
+    fn dotted_name(expr: &rExpr) -> Option<String> {
+        match expr {
+            rExpr::Name(name) => Some(name.id.to_string()),
+            rExpr::Attribute(att) => {
+                Some(format!("{}.{}", Self::dotted_name(&att.value)?, att.attr))
+            }
+            _ => None,
+        }
+    }
+
     fn find_in_ctrs(&self, nam: &Name) -> Option<Name> {
+        if let Some(ctr) = class_ctr(&self.ctr_aliases, nam) {
+            return Some(ctr);
+        }
+        if self.book.ctrs.contains_key(nam) {
+            return Some(nam.clone());
+        }
         for ctr in self.book.ctrs.clone() {
             for ctr_name in ctr.0.split('/') {
                 if nam.to_string() == *ctr_name.to_string() {
@@ -434,38 +1053,166 @@ impl Parser {
         None
     }
 
-    fn parse_main_call(
-        &mut self,
-        value: &FromExpr,
-        name: &String,
-        stmts: &Vec<rStmt>,
-        index: usize,
-    ) -> Option<FromExpr> {
-        if let Some(ctx) = &self.ctx {
-            if ctx.now == CurContext::Main {
-                if let FromExpr::Expr(Expr::Call {
-                    fun,
-                    args: _,
-                    kwargs: _,
-                }) = value.clone()
-                {
-                    if let Expr::Var { nam } = *fun {
-                        if &nam.to_string() == ctx.subs.first().unwrap() {
-                            if let FromExpr::Expr(e) = value.clone() {
-                                return Some(FromExpr::Statement(Stmt::Return {
-                                    term: Box::new(e),
-                                }));
-                            }
-                        }
-                    }
-                }
+    // The class of an annotation, like `Node` for `Node` or `Optional[Node]`.
+    fn annotation_class(&self, annotation: &rExpr) -> Option<Name> {
+        self.class_name(&annotations::optional_type(annotation)?)
+    }
+
+    fn class_adt(&self, class: &Name) -> Option<Name> {
+        class_adt(&self.book, &self.ctr_aliases, class)
+    }
+
+    // The `None` constructor of the datatype of a class or union, like
+    // `Node/None`, if it is annotated as `Optional` somewhere.
+    fn none_ctr(&self, class: &Name) -> Option<Name> {
+        let ctr = Name::new(format!("{}/None", self.class_adt(class)?));
+        self.book.ctrs.contains_key(&ctr).then_some(ctr)
+    }
+
+    // A value given where a class is expected is built with the constructors
+    // of its datatype, like `None` as `Node/None` for `Optional[Node]`.
+    fn fit(&self, expr: &mut Expr, class: Option<&Name>) {
+        if let Some(adt) = class.and_then(|class| self.class_adt(class)) {
+            fit_value(&self.book, &self.ctr_aliases, &adt, expr);
+        }
+    }
+
+    fn fit_fields(&self, ctr: &Name, args: &mut [Expr], kwargs: &mut [(Name, Expr)]) {
+        let Some(adts) = self.field_adts.get(ctr) else {
+            return;
+        };
+        let fields = &self.book.adts[&self.book.ctrs[ctr]].ctrs[ctr];
+
+        let field_adt = |field: &Name| {
+            let index = fields.iter().position(|f| f.nam == *field)?;
+            adts[index].as_ref()
+        };
+        let values = args
+            .iter_mut()
+            .zip(fields.iter().map(|field| &field.nam))
+            .chain(kwargs.iter_mut().map(|(field, arg)| (arg, &*field)));
+        for (arg, field) in values {
+            if let Some(adt) = field_adt(field) {
+                fit_value(&self.book, &self.ctr_aliases, adt, arg);
             }
+        }
+    }
+
+    // The class of a variable or of a field read from it, like `n.next`,
+    // when they are annotated with one.
+    fn var_class(&self, var: &Name) -> Option<Name> {
+        if let Some(class) = self.var_types.get(var) {
+            return Some(class.clone());
+        }
+        let (value, field) = var.rsplit_once('.')?;
+        let class = self.var_class(&Name::new(value))?;
+        let fields = self.field_classes.get(&class)?;
+        fields.iter().find(|(nam, _)| nam == field)?.1.clone()
+    }
+
+    // `x is None` is a match on the `None` constructor of the datatype of `x`,
+    // done by a function of the datatype, since Bend only matches in
+    // statements.
+    fn is_none(&self, value: FromExpr, none: FromExpr, negated: bool) -> Expr {
+        let (FromExpr::Expr(value), FromExpr::Expr(Expr::Era)) = (value, none) else {
+            self.report(Err(PyTypeError::new_err(
+                "'is' can only compare a value with None",
+            )));
+            return Expr::Era;
+        };
+
+        let none = match &value {
+            Expr::Var { nam } => self.var_class(nam).and_then(|class| self.none_ctr(&class)),
+            _ => None,
+        };
+        let Some(none) = none else {
+            self.report(Err(PyTypeError::new_err(
+                "Can't know if the value is None, annotate it with 'Optional' of its class",
+            )));
+            return Expr::Era;
+        };
+
+        let adt = &self.book.ctrs[&none];
+        let is_none = Expr::Call {
+            fun: Box::new(Expr::Var {
+                nam: Name::new(format!("benda/is_none/{}", adt)),
+            }),
+            args: vec![value],
+            kwargs: vec![],
+        };
+
+        match negated {
+            true => Expr::Opr {
+                op: Op::EQ,
+                lhs: Box::new(is_none),
+                rhs: Box::new(Expr::Num {
+                    val: fun::Num::U24(0),
+                }),
+            },
+            false => is_none,
+        }
+    }
+
+    // The datatype matched, from the class of the subject or else the one
+    // with all the classes of the cases, which may be part of many unions.
+    fn match_adt(&self, m: &StmtMatch) -> Option<Name> {
+        let subject = match m.subject.as_ref() {
+            rExpr::Name(name) => self.var_class(&Name::new(escape(&name.id))),
+            _ => None,
+        };
+        if let Some(adt) = subject.and_then(|class| self.class_adt(&class)) {
+            return Some(adt);
+        }
+
+        let classes: Vec<Name> = m
+            .cases
+            .iter()
+            .filter_map(|case| match &case.pattern {
+                rPattern::MatchClass(class) => self.class_name(&class.cls),
+                _ => None,
+            })
+            .collect();
+        let first = self.class_adt(classes.first()?);
+        let covers = |adt: &Name| {
+            classes
+                .iter()
+                .all(|class| self.adt_ctr(adt, class).is_some())
+        };
+
+        match first {
+            Some(adt) if covers(&adt) => Some(adt),
+            _ => self.book.adts.keys().find(|adt| covers(adt)).cloned(),
+        }
+    }
+
+    // The constructor of a datatype built from a class.
+    fn adt_ctr(&self, adt: &Name, class: &Name) -> Option<Name> {
+        self.book
+            .adts
+            .get(adt)?
+            .ctrs
+            .keys()
+            .find(|ctr| self.ctr_aliases.get(*ctr).unwrap_or(ctr) == class)
+            .cloned()
+    }
+
+    // `return None` in a function annotated to return `Optional` of a class.
+    fn fill_returned_none(&self, fun_def: &StmtFunctionDef, body: &mut Stmt) {
+        let class = fun_def
+            .returns
+            .as_deref()
+            .and_then(|ann| self.annotation_class(ann));
+        let Some(class) = class else {
+            return;
+        };
 
-            if ctx.now == CurContext::Main && !ctx.vars.contains(&name) {
-                return self.parse_vec(stmts, index + 1);
+        let mut stmts = vec![body];
+        while let Some(stmt) = stmts.pop() {
+            match stmt {
+                Stmt::Return { term } => self.fit(term, Some(&class)),
+                stmt => stmts.extend(stmt_children_mut(stmt)),
             }
         }
-        None
     }
 
     fn parse_if(&mut self, stmt_if: &StmtIf, stmts: &Vec<rStmt>, index: usize) -> Option<FromExpr> {
@@ -498,36 +1245,14 @@ impl Parser {
         }
     }
 
+    // Expressions alone, like docstrings, have no effect in Bend.
     fn parse_stmt_expr(
         &mut self,
-        expr: &StmtExpr,
+        _expr: &StmtExpr,
         stmts: &Vec<rStmt>,
         index: usize,
     ) -> Option<FromExpr> {
-        if let Some(ctx) = &self.ctx {
-            if ctx.now == CurContext::Main {
-                let val = self.parse_expr_type(*expr.value.clone());
-
-                if let Some(FromExpr::Expr(call)) = val {
-                    if let Expr::Call {
-                        fun,
-                        args: _,
-                        kwargs: _,
-                    } = call.clone()
-                    {
-                        if let imp::Expr::Var { nam } = *fun {
-                            if nam.to_string() == *ctx.subs.first().unwrap() {
-                                return Some(FromExpr::Statement(Stmt::Return {
-                                    term: Box::new(call),
-                                }));
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        None
+        self.parse_vec(stmts, index + 1)
     }
 
     fn parse_assign_stmt(
@@ -537,52 +1262,157 @@ impl Parser {
         index: usize,
     ) -> Option<FromExpr> {
         let value = self.parse_assign(assign).unwrap();
-        let name = assign
-            .targets
-            .first()
-            .unwrap()
-            .clone()
-            .name_expr()
-            .unwrap()
-            .id
-            .to_string();
+        let pat = self.parse_assign_pattern(assign.targets.first().unwrap());
 
-        if let Some(main_call) = self.parse_main_call(&value, &name, stmts, index) {
-            return Some(main_call);
+        // The class of the value, for the methods called on the variable.
+        if let (imp::AssignPattern::Var(name), FromExpr::Expr(val)) = (&pat, &value) {
+            if let Some(class) = self.value_class(val) {
+                self.var_types.insert(name.clone(), class);
+            }
         }
 
         let nxt = self.parse_vec(stmts, index + 1);
 
-        if let FromExpr::Expr(Expr::Call {
-            fun,
-            args: _,
-            kwargs: _,
-        }) = value.clone()
+        if let (imp::AssignPattern::Var(name), FromExpr::Expr(Expr::Call { fun, .. })) =
+            (&pat, value.clone())
         {
             if let Expr::Var { nam } = *fun {
-                if nam.to_string() == "switch" {
-                    return self.parse_switch(&name, &nxt, stmts, &index);
+                if unescape(&nam) == "switch" {
+                    return self.parse_switch(&name.to_string(), &nxt, stmts, &index);
                 }
             }
         }
 
         if let FromExpr::Expr(val) = value {
-            return Some(FromExpr::Statement(imp::Stmt::Assign {
-                pat: imp::AssignPattern::Var(Name::new(name)),
-                val: Box::new(val),
-                nxt: nxt.map(|n| {
-                    if let FromExpr::Statement(n) = n {
-                        return Box::new(n);
-                    }
+            let mut nxt = nxt.map(|n| {
+                if let FromExpr::Statement(n) = n {
+                    return Box::new(n);
+                }
 
-                    todo!()
-                }),
+                todo!()
+            });
+
+            // In `a = b = 0` the other targets get the value of the first one.
+            for target in assign.targets.iter().skip(1).rev() {
+                let val = match &pat {
+                    imp::AssignPattern::Var(nam) => Expr::Var { nam: nam.clone() },
+                    _ => val.clone(),
+                };
+                nxt = Some(Box::new(imp::Stmt::Assign {
+                    pat: self.parse_assign_pattern(target),
+                    val: Box::new(val),
+                    nxt,
+                }));
+            }
+
+            return Some(FromExpr::Statement(imp::Stmt::Assign {
+                pat,
+                val: Box::new(val),
+                nxt,
             }));
         }
 
         Some(value)
     }
 
+    // `x += 1` is written as `x = x + 1`, so it gets the same types and
+    // bignum operations as the binary operation.
+    fn parse_aug_assign(
+        &mut self,
+        aug: &StmtAugAssign,
+        stmts: &Vec<rStmt>,
+        index: usize,
+    ) -> Option<FromExpr> {
+        let imp::AssignPattern::Var(nam) = self.parse_assign_pattern(&aug.target) else {
+            return self.parse_vec(stmts, index + 1);
+        };
+        let target = Expr::Var { nam: nam.clone() };
+        let Some(FromExpr::Expr(value)) = self.parse_expr_type(*aug.value.clone()) else {
+            self.report(Err(PySyntaxError::new_err(format!(
+                "Could not parse the value added to '{}'",
+                unescape(&nam)
+            ))));
+            return self.parse_vec(stmts, index + 1);
+        };
+
+        let val = Self::operation(&aug.op, target.clone(), value);
+
+        Some(FromExpr::Statement(imp::Stmt::Assign {
+            pat: imp::AssignPattern::Var(nam.clone()),
+            val: Box::new(val),
+            nxt: self.parse_next(stmts, index),
+        }))
+    }
+
+    // `x: int = 0` is a plain assignment, and `x: int` alone does nothing.
+    fn parse_ann_assign(
+        &mut self,
+        ann: &StmtAnnAssign,
+        stmts: &Vec<rStmt>,
+        index: usize,
+    ) -> Option<FromExpr> {
+        if let rExpr::Name(name) = ann.target.as_ref() {
+            self.annotate_var(Name::new(escape(&name.id)), &ann.annotation);
+        }
+
+        let Some(value) = &ann.value else {
+            return self.parse_vec(stmts, index + 1);
+        };
+        let pat = self.parse_assign_pattern(&ann.target);
+        let Some(FromExpr::Expr(mut val)) = self.parse_expr_type(*value.clone()) else {
+            self.report(Err(PySyntaxError::new_err(
+                "Could not parse the value of the annotated assignment",
+            )));
+            return self.parse_vec(stmts, index + 1);
+        };
+        self.fit(&mut val, self.annotation_class(&ann.annotation).as_ref());
+
+        Some(FromExpr::Statement(imp::Stmt::Assign {
+            pat,
+            val: Box::new(val),
+            nxt: self.parse_next(stmts, index),
+        }))
+    }
+
+    fn annotate_var(&mut self, var: Name, annotation: &rExpr) {
+        // The fields of `Optional[Point]` are the ones of `Point`, and the
+        // classes of other modules are annotated like `geo.Vec`.
+        if let Some(typ) = self.annotation_class(annotation) {
+            self.var_types.insert(var, typ);
+        }
+    }
+
+    fn parse_next(&mut self, stmts: &Vec<rStmt>, index: usize) -> Option<Box<Stmt>> {
+        match self.parse_vec(stmts, index + 1) {
+            Some(FromExpr::Statement(nxt)) => Some(Box::new(nxt)),
+            _ => None,
+        }
+    }
+
+    // The target of an assignment, a name or a tuple of them, like the
+    // `a, b` of `a, b = b, a`. Bend values can't be changed, so attributes
+    // and items of collections can't be assigned.
+    fn parse_assign_pattern(&self, target: &rExpr) -> imp::AssignPattern {
+        let err = match target {
+            rExpr::Name(name) if name.id.as_str() == "_" => return imp::AssignPattern::Eraser,
+            rExpr::Name(name) => return imp::AssignPattern::Var(Name::new(escape(&name.id))),
+            rExpr::Tuple(tup) => {
+                let pats = tup.elts.iter().map(|elt| self.parse_assign_pattern(elt));
+                return imp::AssignPattern::Tup(pats.collect());
+            }
+            rExpr::Attribute(att) => PyTypeError::new_err(format!(
+                "Cannot assign to the attribute '{}', since Bend values can't be changed. Make a new value instead",
+                att.attr
+            )),
+            rExpr::Subscript(_) => PyTypeError::new_err(
+                "Cannot assign to an item of a collection, since Bend values can't be changed. Make a new collection instead",
+            ),
+            _ => PySyntaxError::new_err("Only names and tuples of them can be assigned to"),
+        };
+        self.report(Err(err));
+        imp::AssignPattern::Eraser
+    }
+
     fn parse_vec(&mut self, stmts: &Vec<rStmt>, index: usize) -> Option<FromExpr> {
         let stmt = match stmts.get(index) {
             Some(s) => s,
@@ -593,6 +1423,8 @@ impl Parser {
 
         match stmt {
             rStmt::Assign(assign) => self.parse_assign_stmt(assign, stmts, index),
+            rStmt::AugAssign(aug) => self.parse_aug_assign(aug, stmts, index),
+            rStmt::AnnAssign(ann) => self.parse_ann_assign(ann, stmts, index),
             rStmt::If(stmt_if) => self.parse_if(stmt_if, stmts, index),
             rStmt::Return(r) => match &r.value {
                 Some(val) => {
@@ -614,6 +1446,9 @@ impl Parser {
                 }
                 None
             }
+            rStmt::FunctionDef(fun_def) if !self.scopes.is_empty() => {
+                self.parse_nested_def(fun_def, stmts, index)
+            }
             _ => None,
         }
     }
@@ -626,7 +1461,7 @@ impl Parser {
                     nam
                 );
             } else {
-                panic!("Repeated datatype '{}'", nam);
+                panic!("Repeated datatype '{}'", unescape(&nam));
             }
         } else {
             for ctr in adt.ctrs.keys() {
@@ -641,7 +1476,7 @@ impl Parser {
                                 e.key()
                             );
                         } else {
-                            panic!("Repeated constructor '{}'", e.key());
+                            panic!("Repeated constructor '{}'", unescape(e.key()));
                         }
                     }
                 }
@@ -650,19 +1485,44 @@ impl Parser {
         self.book.adts.insert(nam.clone(), adt);
     }
 
-    // Creates a Bend Definition for each argument for the annotaded function.
-    fn parse_fun_args(&mut self) {
-        for (name, expr) in &self.fun_args {
-            let u_type = expr.clone().to_fun();
+    // Creates a Bend Definition for each argument for the annotaded function,
+    // and for each constant of the module used by the parsed functions.
+    fn parse_fun_args(&mut self, fun: &str) {
+        // A `None` given to a parameter annotated with `Optional`.
+        let fun = Name::new(fun);
+        for index in 0..self.fun_args.len() {
+            let param = Name::new(self.fun_args[index].0.clone());
+            let class = self.fun_params[&fun]
+                .iter()
+                .position(|(name, _)| *name == param)
+                .and_then(|index| self.param_classes[&fun][index].clone());
+
+            let mut expr = std::mem::replace(&mut self.fun_args[index].1, Expr::Era);
+            self.fit(&mut expr, class.as_ref());
+            self.fun_args[index].1 = expr;
+        }
+
+        for (name, expr) in self.fun_args.iter_mut().chain(&mut self.constants) {
+            // The value is moved out, since cloning or dropping a deep expression
+            // would overflow the stack. Only the name is used afterwards.
+            let expr = std::mem::replace(expr, Expr::Era);
+
+            let body = match value_to_fun(expr, &self.book, &self.ctr_aliases, &self.field_adts) {
+                Ok(body) => body,
+                Err(err) => {
+                    let err = format!("Could not convert '{}': {}", name, err);
+                    self.error
+                        .borrow_mut()
+                        .get_or_insert(PyTypeError::new_err(err));
+                    fun::Term::Era
+                }
+            };
 
             let nam = Name::new(name.to_string());
 
             let def = fun::Definition {
                 name: nam.clone(),
-                rules: vec![Rule {
-                    pats: vec![],
-                    body: u_type,
-                }],
+                rules: vec![Rule { pats: vec![], body }],
                 builtin: false,
             };
 
@@ -670,218 +1530,1111 @@ impl Parser {
         }
     }
 
-    // This function searchs for the Call of the annotaded function.
-    // It uses the Call to create a Bend main.
-    pub fn parse_main(&mut self, fun_name: &str, py_args: &[String]) -> Option<imp::Definition> {
-        self.ctx = Some(Context {
-            now: CurContext::Main,
-            vars: py_args.to_vec(),
-            subs: vec![fun_name.to_string()],
-        });
+    // The main of Bend calls the annotated function with the values it was
+    // given, which are stored in definitions named after its parameters.
+    pub fn parse_main(&mut self, fun_name: &str) -> imp::Definition {
+        self.parse_fun_args(fun_name);
+
+        let args = self
+            .fun_args
+            .iter()
+            .map(|(arg, _)| Expr::Var {
+                nam: Name::new(arg),
+            })
+            .collect();
+
+        imp::Definition {
+            name: Name::new("main"),
+            params: vec![],
+            body: Stmt::Return {
+                term: Box::new(Expr::Call {
+                    fun: Box::new(Expr::Var {
+                        nam: Name::new(fun_name),
+                    }),
+                    args,
+                    kwargs: vec![],
+                }),
+            },
+        }
+    }
 
-        self.parse_fun_args();
+    // Both `@dataclass` and `@dataclasses.dataclass`, with or without options.
+    fn is_dataclass(class: &StmtClassDef) -> bool {
+        class.decorator_list.iter().any(|dec| {
+            let dec = match dec {
+                rExpr::Call(call) => call.func.as_ref(),
+                dec => dec,
+            };
+            match dec {
+                rExpr::Name(nam) => nam.id.as_str() == "dataclass",
+                rExpr::Attribute(att) => att.attr.as_str() == "dataclass",
+                _ => false,
+            }
+        })
+    }
 
-        for (index, stmt) in self.statements.clone().iter().skip(self.index).enumerate() {
-            if let rStmt::Assign(assi) = stmt {
-                if let rExpr::Name(target) = assi.targets.first().unwrap() {
-                    if py_args.contains(target.id.as_ref()) {
-                        let body = self.parse_vec(&self.statements.clone(), index);
+    // The Bend name of a class used in an annotation or a union, like
+    // `Circle` or `shapes.Circle`.
+    fn class_name(&self, expr: &rExpr) -> Option<Name> {
+        match expr {
+            rExpr::Name(name) => Some(
+                self.resolve(&name.id)
+                    .unwrap_or_else(|| Name::new(escape(&name.id))),
+            ),
+            rExpr::Attribute(att) => {
+                let module = self.imported_module(&att.value)?;
+                Some(self.global_name(module, &att.attr))
+            }
+            _ => None,
+        }
+    }
 
-                        if let Some(FromExpr::Statement(st)) = body {
-                            return Some(imp::Definition {
-                                name: Name::new("main"),
-                                params: vec![],
-                                body: st,
-                            });
-                        }
-                    }
+    fn parse_class_def(&mut self, class: &StmtClassDef) {
+        if !Self::is_dataclass(class) {
+            return;
+        }
+
+        let name = self.global_name(self.module, &class.name);
+
+        // Registered classes were already collected by `parse_user_adts`.
+        if self.user_adts.iter().any(|(adt, _)| *adt == name.as_ref()) {
+            return;
+        }
+
+        // A field is recursive, and so folded by Bend, when its annotation
+        // is the class itself or a union it is part of, like `Optional[Node]`.
+        let mut self_types: Vec<Name> = self
+            .unions
+            .iter()
+            .filter(|(_, members)| members.contains(&name))
+            .map(|(alias, _)| alias.clone())
+            .collect();
+        self_types.push(name.clone());
+
+        let mut fields: Vec<CtrField> = vec![];
+        let mut types: Vec<(Name, Option<NumType>)> = vec![];
+        let mut classes: Vec<(Name, Option<Name>)> = vec![];
+
+        for stmt in &class.body {
+            match stmt {
+                rStmt::AnnAssign(assign) if !annotations::is_class_var(&assign.annotation) => {
+                    let rExpr::Name(nam) = assign.target.as_ref() else {
+                        continue;
+                    };
+
+                    let rec = annotations::union_members(&assign.annotation)
+                        .iter()
+                        .filter_map(|member| self.class_name(member))
+                        .any(|member| self_types.contains(&member));
+
+                    let ctr_field = CtrField {
+                        nam: Name::new(escape(&nam.id)),
+                        rec,
+                    };
+
+                    let typ = NumType::from_annotation(&assign.annotation, self.bignum);
+                    types.push((ctr_field.nam.clone(), typ));
+                    let class = self.annotation_class(&assign.annotation);
+                    classes.push((ctr_field.nam.clone(), class));
+                    fields.push(ctr_field);
                 }
+                // Docstrings, `pass`, methods and class variables are not fields.
+                _ => {}
+            }
+        }
 
-                if let rExpr::Call(call) = *assi.clone().value {
-                    if let rExpr::Name(func) = *call.func {
-                        if fun_name == func.id.to_string() {
-                            if call.args.is_empty() {
-                                panic!("The function must have arguments for Bend can run it.");
-                            } else {
-                                let new_body = self.parse_vec(&self.statements.clone(), index);
-
-                                if let Some(FromExpr::Statement(st)) = new_body {
-                                    return Some(imp::Definition {
-                                        name: Name::new("main"),
-                                        params: vec![],
-                                        body: st,
-                                    });
-                                }
-
-                                let mut new_args: Vec<Expr> = vec![];
-
-                                for arg in self.fun_args.clone() {
-                                    new_args.push(Expr::Var {
-                                        nam: Name::new(arg.0),
-                                    })
-                                }
-
-                                let first = Stmt::Return {
-                                    term: Box::new(Expr::Call {
-                                        fun: Box::new(imp::Expr::Var {
-                                            nam: Name::new(fun_name.to_string()),
-                                        }),
-                                        args: new_args,
-                                        kwargs: vec![],
-                                    }),
-                                };
-                                return Some(imp::Definition {
-                                    name: Name::new("main"),
-                                    params: vec![],
-                                    body: first,
-                                });
-                            }
-                        }
+        self.field_types.insert(name.clone(), types);
+        self.field_classes.insert(name.clone(), classes);
+        self.classes.insert(name, fields);
+    }
+
+    // Collects the classes registered with `benda.register_adt`, which are
+    // already given with their Bend names.
+    fn parse_user_adts(&mut self) {
+        for (name, fields) in self.user_adts.clone() {
+            let fields = fields
+                .into_iter()
+                .map(|(field, rec)| CtrField {
+                    nam: Name::new(escape(&field)),
+                    rec,
+                })
+                .collect();
+
+            self.classes.insert(Name::new(name), fields);
+        }
+    }
+
+    // Collects the unions of the modules, like `Shape = Circle | Square`,
+    // `Union[Circle, Square]` or `type Shape = Circle | Square`.
+    fn parse_unions(&mut self) {
+        let classes = self.data_classes();
+        for module in 0..self.modules.len() {
+            self.module = module;
+            for stmt in &self.modules[module].statements {
+                let is_class = |member: &rExpr| {
+                    self.class_name(member)
+                        .is_some_and(|class| classes.contains(&class))
+                };
+                let Some((name, value)) = annotations::type_alias(stmt, is_class) else {
+                    continue;
+                };
+
+                let members = annotations::union_members(value);
+                let alias = self.global_name(module, name);
+                if members.iter().any(annotations::is_none) {
+                    self.optional_classes.push(alias.clone());
+                }
+
+                let members = members
+                    .iter()
+                    .filter_map(|member| self.class_name(member))
+                    .collect();
+                self.unions.push((alias, members));
+            }
+        }
+    }
+
+    // The dataclasses and registered classes of all the modules, which are
+    // not parsed yet when the unions are collected.
+    fn data_classes(&self) -> Vec<Name> {
+        let mut classes: Vec<Name> = self
+            .user_adts
+            .iter()
+            .map(|(name, _)| Name::new(name))
+            .collect();
+        for (module, parsed) in self.modules.iter().enumerate() {
+            for stmt in &parsed.statements {
+                if let rStmt::ClassDef(class) = stmt {
+                    if Self::is_dataclass(class) {
+                        classes.push(self.global_name(module, &class.name));
                     }
                 }
             }
         }
+        classes
+    }
 
-        None
+    // Collects the classes and unions annotated as `Optional`, or as a union
+    // with `None`, in any module.
+    fn parse_optionals(&mut self) {
+        for module in 0..self.modules.len() {
+            self.module = module;
+            for annotation in annotations::optional_annotations(&self.modules[module].statements) {
+                for member in annotations::union_members(&annotation) {
+                    if let Some(class) = self.class_name(&member) {
+                        self.optional_classes.push(class);
+                    }
+                }
+            }
+        }
     }
 
-    fn parse_class_def(&mut self, class: &StmtClassDef) {
-        let is_dataclass = class.decorator_list.iter().any(|exp| {
-            if let rExpr::Name(nam) = exp {
-                if nam.id.to_string() == "dataclass" {
-                    return true;
+    // Creates the datatypes of the classes. Every class is a datatype of its
+    // own, and the classes of a union are also the constructors of its
+    // datatype, named after it, like `Shape/Circle`.
+    fn parse_datatypes(&mut self) {
+        let classes = std::mem::take(&mut self.classes);
+
+        // Unions of other types, like `Number = int | float`, are not datatypes.
+        let unions: Vec<(Name, Vec<Name>)> = self
+            .unions
+            .iter()
+            .filter(|(_, members)| members.iter().all(|member| classes.contains_key(member)))
+            .cloned()
+            .collect();
+
+        for (alias, members) in unions {
+            let mut adt = Adt {
+                ctrs: IndexMap::new(),
+                builtin: false,
+            };
+            let optional = [&alias]
+                .into_iter()
+                .chain(&members)
+                .any(|class| self.optional_classes.contains(class));
+
+            for member in members {
+                // Constructors of other modules are named after their class.
+                let class = member.rsplit('/').next().unwrap();
+                let ctr = Name::new(format!("{}/{}", alias, class));
+
+                if let Some(types) = self.field_types.get(&member) {
+                    self.field_types.insert(ctr.clone(), types.clone());
                 }
+                self.ctr_aliases.insert(ctr.clone(), member.clone());
+                adt.ctrs.insert(ctr, classes[&member].clone());
+            }
+
+            if optional {
+                self.add_none(&alias, &mut adt);
+            }
+            self.add_adt(alias, adt);
+        }
+
+        for (name, fields) in classes {
+            let mut adt = Adt {
+                ctrs: IndexMap::new(),
+                builtin: false,
+            };
+            adt.ctrs.insert(name.clone(), fields);
+
+            if self.optional_classes.contains(&name) {
+                self.add_none(&name, &mut adt);
+            }
+            self.add_adt(name, adt);
+        }
+
+        for (class, fields) in &self.field_classes {
+            let adts: Vec<Option<Name>> = fields
+                .iter()
+                .map(|(_, class)| self.class_adt(class.as_ref()?))
+                .collect();
+            let ctrs = self
+                .ctr_aliases
+                .iter()
+                .filter(|(_, member)| *member == class)
+                .map(|(ctr, _)| ctr.clone());
+            for ctr in ctrs.chain([class.clone()]).collect::<Vec<_>>() {
+                self.field_adts.insert(ctr, adts.clone());
             }
-            false
+        }
+    }
+
+    // Adds the `None` constructor to a datatype, like `Node/None`, and the
+    // function telling if a value is it, used by `is None`.
+    fn add_none(&mut self, name: &Name, adt: &mut Adt) {
+        let none = Name::new(format!("{}/None", name));
+        adt.ctrs.insert(none.clone(), vec![]);
+
+        let value = Name::new("value");
+        let ret = |val| Stmt::Return {
+            term: Box::new(Expr::Num {
+                val: fun::Num::U24(val),
+            }),
+        };
+        self.definitions.push(imp::Definition {
+            name: Name::new(format!("benda/is_none/{}", name)),
+            params: vec![value.clone()],
+            body: Stmt::Match {
+                arg: Box::new(Expr::Var { nam: value.clone() }),
+                bnd: Some(value),
+                with_bnd: vec![],
+                with_arg: vec![],
+                arms: vec![
+                    MatchArm {
+                        lft: Some(none),
+                        rgt: ret(1),
+                    },
+                    MatchArm {
+                        lft: None,
+                        rgt: ret(0),
+                    },
+                ],
+                nxt: None,
+            },
         });
+    }
+
+    // Classes deriving from `Enum`, `IntEnum` or `StrEnum`, with or without
+    // their module.
+    fn is_enum(class: &StmtClassDef) -> bool {
+        class.bases.iter().any(|base| {
+            let base = match base {
+                rExpr::Name(nam) => nam.id.as_str(),
+                rExpr::Attribute(att) => att.attr.as_str(),
+                _ => return false,
+            };
+            matches!(base, "Enum" | "IntEnum" | "StrEnum")
+        })
+    }
+
+    // An enum becomes a datatype with a constructor without fields for each
+    // member, like `Color/RED` for `RED = 1`. Names starting with an
+    // underscore and methods are not members.
+    fn parse_enum(&mut self, class: &StmtClassDef) {
+        let name = self.global_name(self.module, &class.name);
 
-        let iden = class.name.to_string();
         let mut adt = Adt {
             ctrs: IndexMap::new(),
             builtin: false,
         };
 
-        if is_dataclass {
-            for stmt in &class.body {
-                match stmt {
-                    rStmt::AnnAssign(assign) => {
-                        let mut target = String::default();
-                        if let rExpr::Name(nam) = *assign.target.clone() {
-                            target = nam.id.to_string();
-                        }
+        for stmt in &class.body {
+            let targets = match stmt {
+                rStmt::Assign(assign) => assign.targets.iter().collect(),
+                rStmt::AnnAssign(assign) if assign.value.is_some() => vec![assign.target.as_ref()],
+                _ => vec![],
+            };
 
-                        let ctr_field = CtrField {
-                            nam: Name::new(target),
-                            rec: true,
-                        };
+            for target in targets {
+                if let rExpr::Name(member) = target {
+                    if !member.id.starts_with('_') {
+                        let ctr = Name::new(format!("{}/{}", name, escape(&member.id)));
+                        adt.ctrs.insert(ctr, vec![]);
+                    }
+                }
+            }
+        }
 
-                        let new_name = Name::new(iden.to_string());
+        self.add_adt(name, adt);
+    }
 
-                        match adt.ctrs.get_mut(&new_name) {
-                            Some(vec) => {
-                                vec.push(ctr_field);
-                            }
-                            None => {
-                                adt.ctrs.insert(new_name.clone(), vec![ctr_field]);
-                            }
-                        }
-                    }
-                    _ => todo!(),
+    // The constructor of an enum member, like `Color/RED` for `Color.RED`.
+    fn enum_member(&self, att: &ExprAttribute) -> Option<Name> {
+        let adt = self.global_class(&att.value)?;
+        let ctr = Name::new(format!("{}/{}", adt, escape(&att.attr)));
+        (self.book.ctrs.get(&ctr) == Some(&adt)).then_some(ctr)
+    }
+
+    // The class an expression names, unless it is a local variable.
+    fn global_class(&self, expr: &rExpr) -> Option<Name> {
+        if let rExpr::Name(name) = expr {
+            if self.is_local(&name.id) {
+                return None;
+            }
+        }
+        self.class_name(expr)
+    }
+
+    fn method(&self, class: &Name, method: &str) -> Option<Name> {
+        let def = Name::new(format!("{}/{}", class, escape(method)));
+        self.methods.get(class)?.contains(&def).then_some(def)
+    }
+
+    // A method used through its class, like `Circle.area`.
+    fn class_method(&self, att: &ExprAttribute) -> Option<Name> {
+        let class = self.global_class(&att.value)?;
+        self.method(&class, &att.attr)
+    }
+
+    // The class of a value, from the annotation of a variable, a constructor
+    // or the annotated return of the function called.
+    fn value_class(&self, value: &Expr) -> Option<Name> {
+        match value {
+            Expr::Var { nam } => self.var_types.get(nam).cloned(),
+            Expr::Ctr { name, .. } => Some(self.ctr_aliases.get(name).unwrap_or(name).clone()),
+            Expr::Call { fun, .. } => match fun.as_ref() {
+                Expr::Var { nam } => self.return_classes.get(nam).cloned(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // The method called on a value, like `area` in `c.area()`, with the
+    // value unless the method is static. Its class is the one of the value.
+    fn bound_method(&self, att: &ExprAttribute) -> Option<(Name, Option<Expr>)> {
+        if self.imported_module(&att.value).is_some() || self.class_method(att).is_some() {
+            return None;
+        }
+
+        let Some(FromExpr::Expr(value)) = self.parse_expr_type(*att.value.clone()) else {
+            return None;
+        };
+
+        let method = match self.value_class(&value) {
+            Some(class) => self.method(&class, &att.attr)?,
+            None => {
+                let classes: Vec<String> = self
+                    .methods
+                    .keys()
+                    .filter(|class| self.method(class, &att.attr).is_some())
+                    .map(|class| unescape(class))
+                    .collect();
+                if classes.is_empty() {
+                    return None;
                 }
+                self.report(Err(PyTypeError::new_err(format!(
+                    "Can't know the class of the method '{}' called, annotate the type of the value with one of: {}",
+                    att.attr,
+                    classes.join(", ")
+                ))));
+                return None;
             }
-            self.add_adt(Name::new(iden.clone()), adt);
+        };
+
+        match self.static_methods.contains(&method) {
+            true => Some((method, None)),
+            false => Some((method, Some(value))),
         }
     }
 
-    fn parse_type_alias(&mut self, assign: &StmtAssign) {
-        let iden = assign.targets.first().unwrap();
+    // The methods of a class, by their Bend name, like `Circle/area`. The
+    // object is their first parameter, annotated with the class if it is
+    // not, so that its fields can be read and its methods called. The class
+    // given to a `@classmethod` is not a parameter, its uses are the class.
+    fn class_methods(&self, module: usize, class: &StmtClassDef) -> Vec<(Name, StmtFunctionDef)> {
+        let class_name = self.global_name(module, &class.name);
+
+        class
+            .body
+            .iter()
+            .filter_map(|stmt| match stmt {
+                rStmt::FunctionDef(fun_def) => Some(fun_def),
+                _ => None,
+            })
+            .map(|fun_def| {
+                let mut fun_def = fun_def.clone();
+
+                if Self::has_decorator(&fun_def, "classmethod") {
+                    let args = &mut fun_def.args;
+                    let cls = match args.posonlyargs.is_empty() {
+                        true => args.args.remove(0),
+                        false => args.posonlyargs.remove(0),
+                    };
+                    let mut renamer = Renamer {
+                        from: cls.def.arg,
+                        to: class.name.clone(),
+                    };
+                    fun_def.body = fun_def
+                        .body
+                        .into_iter()
+                        .map(|stmt| renamer.fold_stmt(stmt).unwrap())
+                        .collect();
+                }
+
+                let is_static = Self::is_static(&fun_def);
+                let args = &mut fun_def.args;
+                let first = args.posonlyargs.first_mut().or(args.args.first_mut());
+
+                if let (false, Some(first)) = (is_static, first) {
+                    if first.def.annotation.is_none() {
+                        first.def.annotation = Some(Box::new(rExpr::Name(ExprName {
+                            range: first.def.range,
+                            id: class.name.clone(),
+                            ctx: ExprContext::Load,
+                        })));
+                    }
+                }
 
-        let name: String;
+                let name = Name::new(format!("{}/{}", class_name, escape(&fun_def.name)));
+                (name, fun_def)
+            })
+            .collect()
+    }
 
-        if let rExpr::Name(iden) = iden {
-            name = iden.id.to_string();
-            let mut adt = Adt {
-                ctrs: IndexMap::new(),
-                builtin: false,
-            };
+    // Methods that are not given the object, `@staticmethod`s and `@classmethod`s.
+    fn is_static(fun_def: &StmtFunctionDef) -> bool {
+        Self::has_decorator(fun_def, "staticmethod") || Self::has_decorator(fun_def, "classmethod")
+    }
 
-            let body = self.parse_expr_type(*assign.value.clone());
+    fn has_decorator(fun_def: &StmtFunctionDef, decorator: &str) -> bool {
+        fun_def
+            .decorator_list
+            .iter()
+            .any(|dec| matches!(dec, rExpr::Name(nam) if nam.id.as_str() == decorator))
+    }
 
-            if let Some(FromExpr::CtrField(ctr)) = body {
-                for ct in ctr.clone() {
-                    let new_ctr = self.book.ctrs.swap_remove(&ct.nam);
+    // Both `@bjit` and `@benda.bjit`, with or without options.
+    fn is_bjit(fun_def: &StmtFunctionDef) -> bool {
+        fun_def.decorator_list.iter().any(|dec| {
+            let dec = match dec {
+                rExpr::Call(call) => call.func.as_ref(),
+                dec => dec,
+            };
+            match dec {
+                rExpr::Name(nam) => nam.id.as_str() == "bjit",
+                rExpr::Attribute(att) => att.attr.as_str() == "bjit",
+                _ => false,
+            }
+        })
+    }
 
-                    let new_adt = self.book.adts.swap_remove(&new_ctr.unwrap());
+    // The functions and methods of every module, by their Bend name, with
+    // their module.
+    fn module_functions(&self) -> HashMap<Name, (usize, StmtFunctionDef)> {
+        let mut fun_defs = HashMap::new();
 
-                    let mut ctrs: Vec<CtrField> = vec![];
-                    for ca in new_adt.unwrap().ctrs.values() {
-                        for i in ca {
-                            ctrs.push(i.clone());
+        for (index, module) in self.modules.iter().enumerate() {
+            for stmt in &module.statements {
+                match stmt {
+                    rStmt::FunctionDef(fun_def) => {
+                        let name = Name::new(format!("{}{}", module.prefix, escape(&fun_def.name)));
+                        fun_defs.insert(name, (index, fun_def.clone()));
+                    }
+                    rStmt::ClassDef(class) => {
+                        for (name, fun_def) in self.class_methods(index, class) {
+                            fun_defs.insert(name, (index, fun_def));
                         }
                     }
-
-                    adt.ctrs
-                        .insert(Name::new(format!("{}/{}", name, ct.nam)), ctrs.clone());
+                    _ => {}
                 }
             }
-
-            self.add_adt(Name::new(name), adt);
         }
+
+        fun_defs
     }
 
-    fn parse_function_def(&mut self, fun_def: &StmtFunctionDef) {
-        let mut is_bjit = false;
+    // Parses the annotated function and every function of the package it
+    // reaches, directly or through the functions given to it as arguments.
+    // Helpers without `@bjit` are only parsed if they are pure.
+    fn parse_functions(&mut self, fun: &str) {
+        let fun_defs = self.module_functions();
+
+        let mut pending: Vec<Name> = vec![Name::new(fun)];
+        for (_, value) in &self.fun_args {
+            pending.extend(closures::expr_names(value));
+        }
+
+        let mut parsed: Vec<Name> = vec![];
 
-        for dec in &fun_def.decorator_list {
-            if let rExpr::Name(nam) = dec {
-                if nam.id.to_string() == "bjit" {
-                    is_bjit = true;
+        while let Some(name) = pending.pop() {
+            if parsed.contains(&name) {
+                continue;
+            }
+            let Some((module, fun_def)) = fun_defs.get(&name) else {
+                self.parse_global(&name);
+                continue;
+            };
+            parsed.push(name.clone());
+            self.module = *module;
+
+            if !Self::is_bjit(fun_def) {
+                let is_method = |method: &str| {
+                    self.methods
+                        .keys()
+                        .any(|class| self.method(class, method).is_some())
+                };
+                if let Some(reason) = purity::impurity(fun_def, &is_method) {
+                    self.report(Err(PyTypeError::new_err(format!(
+                        "The function '{}' is called from Bend, but it is not pure: it {}",
+                        unescape(&name),
+                        reason
+                    ))));
+                    continue;
+                }
+            }
+
+            let start = self.definitions.len();
+            self.parse_function_def(name.clone(), fun_def);
+
+            let mut free_vars = vec![];
+            for def in &self.definitions[start..] {
+                free_vars.extend(closures::free_vars(&def.params, &def.body));
+            }
+            for var in &free_vars {
+                if !fun_defs.contains_key(var) && !self.is_defined(var) {
+                    self.check_foreign(var);
                 }
             }
+            pending.extend(free_vars);
         }
+    }
 
-        if !is_bjit {
+    // A name used by the functions that is not a function of the package may
+    // be a constant of its module, like `DEPTH = 10`, which is given to Bend
+    // like the arguments. Other callables, like lambdas and builtins, and
+    // modules can't be given.
+    fn parse_global(&mut self, name: &Name) {
+        if self.is_defined(name) {
             return;
         }
 
-        let args = *fun_def.args.clone();
-        let mut names: Vec<Name> = vec![];
+        // The module defining it, and its name there.
+        let Some((module, local)) = self.modules.iter().find_map(|module| {
+            let local = unescape(name.strip_prefix(module.prefix.as_str())?);
+            module.defined.contains(&local).then_some((module, local))
+        }) else {
+            return;
+        };
+
+        let constant = Python::with_gil(|py| {
+            let value = module.globals.bind(py).get_item(&local).ok()??;
+            if let Some(reason) = unsupported_global(&value) {
+                return Some(Err(PyTypeError::new_err(reason)));
+            }
+
+            overflow::set_argument(py, &local);
+            Some(extract_type(value))
+        });
+
+        match constant {
+            Some(Ok(expr)) => self.constants.push((name.to_string(), expr)),
+            Some(Err(err)) => self.report(Err(global_error(name, err))),
+            None => {}
+        }
+    }
 
-        for arg in args.args {
-            names.push(Name::new(arg.def.arg.to_string()));
+    fn is_defined(&self, name: &Name) -> bool {
+        self.book.defs.contains_key(name)
+            || self.book.ctrs.contains_key(name)
+            || self.book.adts.contains_key(name)
+            || self.definitions.iter().any(|def| def.name == *name)
+            || self
+                .constants
+                .iter()
+                .any(|(constant, _)| *constant == name.to_string())
+    }
+
+    // A name left free in a function of the module, which is not a global
+    // the module defines, may be a module or a function imported from outside
+    // the package, which Bend doesn't know.
+    fn check_foreign(&self, name: &Name) {
+        let module = &self.modules[self.module];
+        let reason = Python::with_gil(|py| {
+            let value = module.globals.bind(py).get_item(unescape(name)).ok()??;
+            unsupported_global(&value)
+        });
+
+        if let Some(reason) = reason {
+            self.report(Err(global_error(name, PyTypeError::new_err(reason))));
         }
+    }
+
+    fn parse_function_def(&mut self, name: Name, fun_def: &StmtFunctionDef) {
+        self.var_types.clear();
+        let (params, signature) = self.parse_params(fun_def);
+        self.signatures.insert(name.clone(), signature);
 
+        self.scopes
+            .push((name.clone(), closures::py_locals(&params, &fun_def.body)));
         let expr = self.parse_vec(&fun_def.body, 0);
+        self.scopes.pop();
 
-        if let Some(FromExpr::Statement(e)) = expr {
+        if let Some(FromExpr::Statement(mut e)) = expr {
+            self.fill_returned_none(fun_def, &mut e);
+            self.open_fields(&mut e, vec![]);
             let def = imp::Definition {
-                name: Name::new(fun_def.name.to_string()),
-                params: names,
+                name,
+                params,
                 body: e,
             };
             self.definitions.push(def);
         }
     }
 
-    // Main function of the library, it parses the Python Module
-    pub fn parse(&mut self, fun: &str, py_args: &[String]) -> String {
-        for stmt in self.statements.clone() {
-            match stmt {
-                rStmt::FunctionDef(fun_def) => self.parse_function_def(&fun_def),
-                // Treats a type alias, example: Type = A | B
-                rStmt::Assign(assign) => self.parse_type_alias(&assign),
-                rStmt::ClassDef(class) => self.parse_class_def(&class),
-                _ => {}
+    // The parameters of a function, in the order Python binds them.
+    fn fun_params(fun_def: &StmtFunctionDef) -> impl Iterator<Item = &ArgWithDefault> {
+        let args = &fun_def.args;
+        args.posonlyargs
+            .iter()
+            .chain(&args.args)
+            .chain(&args.kwonlyargs)
+    }
+
+    fn parse_params(&mut self, fun_def: &StmtFunctionDef) -> (Vec<Name>, Signature) {
+        let mut names: Vec<Name> = vec![];
+        let mut signature = Signature::default();
+
+        for arg in Self::fun_params(fun_def) {
+            let name = Name::new(escape(&arg.def.arg));
+            names.push(name.clone());
+
+            let annotation = arg.def.annotation.as_deref();
+            if let Some(ann) = annotation {
+                self.annotate_var(name, ann);
+            }
+            signature
+                .params
+                .push(annotation.and_then(|ann| NumType::from_annotation(ann, self.bignum)));
+        }
+
+        signature.ret = fun_def
+            .returns
+            .as_deref()
+            .and_then(|ann| NumType::from_annotation(ann, self.bignum));
+
+        (names, signature)
+    }
+
+    // A def inside a function becomes a definition of its own, named after
+    // the functions around it, like `outer/inner`. The variables it captures
+    // become its first parameters, so inside the function its name is bound
+    // to the definition applied to them, which is a lambda Bend can call or
+    // return like any other value.
+    fn parse_nested_def(
+        &mut self,
+        fun_def: &StmtFunctionDef,
+        stmts: &Vec<rStmt>,
+        index: usize,
+    ) -> Option<FromExpr> {
+        let local = Name::new(escape(&fun_def.name));
+        let (outer, _) = self.scopes.last().unwrap();
+        let name = Name::new(format!("{}/{}", outer, local));
+
+        let (mut params, mut signature) = self.parse_params(fun_def);
+
+        self.scopes
+            .push((name.clone(), closures::py_locals(&params, &fun_def.body)));
+        let body = self.parse_vec(&fun_def.body, 0);
+        self.scopes.pop();
+
+        let Some(FromExpr::Statement(mut body)) = body else {
+            self.report(Err(PySyntaxError::new_err(format!(
+                "Could not parse the body of the function '{}'",
+                unescape(&local)
+            ))));
+            return self.parse_vec(stmts, index + 1);
+        };
+        self.fill_returned_none(fun_def, &mut body);
+        self.open_fields(&mut body, vec![]);
+
+        // Recursive calls use the local name, which is bound again inside.
+        let free = closures::free_vars(&params, &body);
+        let recursive = free.contains(&local);
+        let captured: Vec<Name> = free
+            .into_iter()
+            .filter(|nam| *nam != local)
+            .filter(|nam| self.scopes.iter().any(|(_, locals)| locals.contains(nam)))
+            .collect();
+
+        let closure = if captured.is_empty() {
+            Expr::Var { nam: name.clone() }
+        } else {
+            Expr::Call {
+                fun: Box::new(Expr::Var { nam: name.clone() }),
+                args: captured
+                    .iter()
+                    .map(|nam| Expr::Var { nam: nam.clone() })
+                    .collect(),
+                kwargs: vec![],
+            }
+        };
+
+        if recursive {
+            body = Stmt::Assign {
+                pat: imp::AssignPattern::Var(local.clone()),
+                val: Box::new(closure.clone()),
+                nxt: Some(Box::new(body)),
+            };
+        }
+
+        signature.params.splice(0..0, vec![None; captured.len()]);
+        params.splice(0..0, captured);
+        self.signatures.insert(name.clone(), signature);
+        self.definitions
+            .push(imp::Definition { name, params, body });
+
+        Some(FromExpr::Statement(Stmt::Assign {
+            pat: imp::AssignPattern::Var(local),
+            val: Box::new(closure),
+            nxt: self.parse_next(stmts, index),
+        }))
+    }
+
+    // Opens the values whose fields are read, like `p` in `p.x`, right before
+    // the first statement reading them. Values already opened or matched in
+    // the enclosing statements are not opened again.
+    fn open_fields(&self, stmt: &mut Stmt, mut bound: Vec<Name>) {
+        let mut opens: Vec<(Name, Name)> = vec![];
+
+        for var in closures::field_vars(stmt) {
+            // `p.a.b` needs `p` opened for `a` and then `p.a` opened for `b`.
+            let mut prefix = String::new();
+            for (i, part) in var.split('.').enumerate() {
+                if i > 0 {
+                    let value = Name::new(prefix.clone());
+                    if !bound.contains(&value) {
+                        match self.field_adt(&value, part) {
+                            Ok(adt) => {
+                                bound.push(value.clone());
+                                opens.push((adt, value));
+                            }
+                            Err(err) => self.report(Err(err)),
+                        }
+                    }
+                    prefix.push('.');
+                }
+                prefix.push_str(part);
+            }
+        }
+
+        match stmt {
+            Stmt::Match { bnd: Some(bnd), .. } | Stmt::Fold { bnd: Some(bnd), .. } => {
+                bound.push(bnd.clone())
+            }
+            Stmt::Open { var, .. } => bound.push(var.clone()),
+            _ => {}
+        }
+
+        for child in stmt_children_mut(stmt) {
+            self.open_fields(child, bound.clone());
+        }
+
+        for (typ, var) in opens.into_iter().rev() {
+            let nxt = std::mem::replace(stmt, Stmt::Err);
+            *stmt = match self.var_ctr(&var, &typ) {
+                // A value annotated with a class of a union, like `c: Circle`
+                // for `Shape = Circle | Square`, is matched on its constructor.
+                Some(ctr) => Stmt::Match {
+                    arg: Box::new(Expr::Var { nam: var.clone() }),
+                    bnd: Some(var),
+                    with_bnd: vec![],
+                    with_arg: vec![],
+                    arms: vec![
+                        imp::MatchArm {
+                            lft: Some(ctr),
+                            rgt: nxt,
+                        },
+                        imp::MatchArm {
+                            lft: None,
+                            rgt: Stmt::Return {
+                                term: Box::new(Expr::Era),
+                            },
+                        },
+                    ],
+                    nxt: None,
+                },
+                None => Stmt::Open {
+                    typ,
+                    var,
+                    nxt: Box::new(nxt),
+                },
+            };
+        }
+    }
+
+    // The constructor a value is annotated with, when its datatype has others.
+    fn var_ctr(&self, value: &Name, adt: &Name) -> Option<Name> {
+        let typ = self.var_types.get(value)?;
+        let ctr = self.find_in_ctrs(typ)?;
+        (self.book.adts[adt].ctrs.len() > 1).then_some(ctr)
+    }
+
+    // The datatype of a value whose field is read, from its annotation or, if
+    // it has none, the only datatype with a field of that name.
+    fn field_adt(&self, value: &Name, field: &str) -> PyResult<Name> {
+        let adt = match self.var_types.get(value) {
+            Some(typ) => match self.class_adt(typ) {
+                Some(adt) => adt,
+                None => match self.find_in_ctrs(typ) {
+                    Some(ctr) => self.book.ctrs[&ctr].clone(),
+                    None => {
+                        return Err(PyTypeError::new_err(format!(
+                            "Unknown type '{}' of '{}'",
+                            unescape(typ),
+                            unescape(value)
+                        )))
+                    }
+                },
+            },
+            None => {
+                let adts: Vec<&Name> = self
+                    .book
+                    .adts
+                    .iter()
+                    .filter(|(_, adt)| !adt.builtin)
+                    .filter(|(_, adt)| {
+                        adt.ctrs
+                            .values()
+                            .any(|fields| fields.iter().any(|f| f.nam.as_ref() == field))
+                    })
+                    .map(|(name, _)| name)
+                    .collect();
+
+                match adts.as_slice() {
+                    [adt] => (*adt).clone(),
+                    [] => {
+                        return Err(PyAttributeError::new_err(format!(
+                            "No datatype has a field named '{}'",
+                            unescape(field)
+                        )))
+                    }
+                    _ => {
+                        return Err(PyTypeError::new_err(format!(
+                            "Several datatypes have a field named '{}', annotate the type of '{}'",
+                            unescape(field),
+                            unescape(value)
+                        )))
+                    }
+                }
+            }
+        };
+
+        // A class with a `None` constructor is also a constructor of its own
+        // datatype.
+        let ctr_typed = self.var_types.get(value).is_some_and(|typ| {
+            !self.book.adts.contains_key(typ) || self.book.ctrs.contains_key(typ)
+        });
+        if self.book.adts[&adt].ctrs.len() > 1 && !ctr_typed {
+            return Err(PyTypeError::new_err(format!(
+                "Cannot read the field '{}' of '{}', since '{}' has more than one constructor. Use a match instead",
+                unescape(field),
+                unescape(value),
+                unescape(&adt)
+            )));
+        }
+
+        Ok(adt)
+    }
+
+    // The types of the values given to the annotated function are used for
+    // its parameters without annotations.
+    fn infer_num_types(&mut self, fun: &str) {
+        let mut signatures = std::mem::take(&mut self.signatures);
+
+        if let Some(signature) = signatures.get_mut(&Name::new(fun)) {
+            for (param, (_, value)) in signature.params.iter_mut().zip(&self.fun_args) {
+                match (&param, value) {
+                    (None, Expr::Num { val }) => *param = Some(NumType::from_num(val)),
+                    // Python ints are given as lists when bignums are enabled.
+                    (None, Expr::Lst { .. }) if self.bignum => *param = Some(NumType::Big),
+                    _ => {}
+                }
+            }
+        }
+
+        let field_types = std::mem::take(&mut self.field_types);
+        let mut num_types = NumTypes::new(signatures, field_types, self.bignum, self.overflow);
+        let inferred = num_types.infer(&mut self.definitions);
+        self.report(inferred);
+        self.signatures = num_types.into_signatures();
+    }
+
+    // Whether the annotated function returns a bignum, which Bend gives back
+    // as a list.
+    pub fn returns_bignum(&self, fun: &str) -> bool {
+        self.signatures
+            .get(&Name::new(fun))
+            .is_some_and(|sig| sig.ret == Some(NumType::Big))
+    }
+
+    // The type of the value the annotated function returns, from the
+    // constructors it returns, for when it has no return annotation.
+    // Constructors with the same tag and number of fields can only be told
+    // apart by it.
+    pub fn return_hint(&self, fun: &str) -> Option<Hint> {
+        self.returned_hint(&Name::new(fun), &mut vec![])
+    }
+
+    // Every return of the function must give the same type, except the
+    // recursive calls, which give whatever the others do.
+    fn returned_hint(&self, fun: &Name, stack: &mut Vec<Name>) -> Option<Hint> {
+        let def = self.definitions.iter().find(|def| def.name == *fun)?;
+        stack.push(fun.clone());
+
+        let mut found: Option<Hint> = None;
+        let mut agree = true;
+        for expr in returned_exprs(&def.body) {
+            if let Expr::Call { fun, .. } = expr {
+                if matches!(fun.as_ref(), Expr::Var { nam } if stack.contains(nam)) {
+                    continue;
+                }
+            }
+
+            match (self.expr_hint(expr, stack), &found) {
+                (Some(hint), Some(found)) if hint == *found => {}
+                (Some(hint), None) => found = Some(hint),
+                _ => agree = false,
+            }
+        }
+
+        stack.pop();
+        found.filter(|_| agree)
+    }
+
+    fn expr_hint(&self, expr: &Expr, stack: &mut Vec<Name>) -> Option<Hint> {
+        match expr {
+            Expr::Ctr { name, .. } => self.book.ctrs.get(name).cloned().map(Hint::Adt),
+            Expr::Str { .. } => Some(Hint::Adt(Name::new("String"))),
+            Expr::Lst { .. } => Some(Hint::Adt(Name::new("List"))),
+            // The length of a tuple is needed to read it back.
+            Expr::Tup { els } => Some(Hint::Tup(
+                els.iter().map(|el| self.expr_hint(el, stack)).collect(),
+            )),
+            Expr::Call { fun, .. } => match fun.as_ref() {
+                Expr::Var { nam } => self.returned_hint(nam, stack),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn book(&self) -> &Book {
+        &self.book
+    }
+
+    pub fn ctr_aliases(&self) -> &HashMap<Name, Name> {
+        &self.ctr_aliases
+    }
+
+    // Main function of the library, it parses the Python Module. The function
+    // is given by its Bend name, like `Circle/area` for a method.
+    pub fn parse(&mut self, fun: &str) -> PyResult<Term> {
+        for (name, (module, fun_def)) in self.module_functions() {
+            self.module = module;
+            let params = Self::fun_params(&fun_def)
+                .map(|arg| {
+                    (
+                        Name::new(escape(&arg.def.arg)),
+                        arg.default.as_deref().cloned(),
+                    )
+                })
+                .collect();
+            let classes = Self::fun_params(&fun_def)
+                .map(|arg| {
+                    let annotation = arg.def.annotation.as_deref()?;
+                    self.annotation_class(annotation)
+                })
+                .collect();
+            if let Some(class) = fun_def
+                .returns
+                .as_deref()
+                .and_then(|ann| self.annotation_class(ann))
+            {
+                self.return_classes.insert(name.clone(), class);
+            }
+            self.fun_params.insert(name.clone(), params);
+            self.param_classes.insert(name, classes);
+        }
+
+        for module in 0..self.modules.len() {
+            for stmt in &self.modules[module].statements {
+                if let rStmt::ClassDef(class) = stmt {
+                    let class_name = self.global_name(module, &class.name);
+                    let methods = self.class_methods(module, class);
+                    for (name, fun_def) in &methods {
+                        if Self::is_static(fun_def) {
+                            self.static_methods.push(name.clone());
+                        }
+                    }
+                    let methods = methods.into_iter().map(|(name, _)| name).collect();
+                    self.methods.insert(class_name, methods);
+                }
+            }
+        }
+
+        // The datatypes come first, since functions use their constructors.
+        // The unions of every module are collected before the classes, whose
+        // fields may refer to them, and may use the classes of other modules.
+        self.parse_unions();
+        self.parse_optionals();
+        self.parse_user_adts();
+        for module in 0..self.modules.len() {
+            self.module = module;
+            for stmt in self.modules[module].statements.clone() {
+                match stmt {
+                    rStmt::ClassDef(class) if Self::is_enum(&class) => self.parse_enum(&class),
+                    rStmt::ClassDef(class) => self.parse_class_def(&class),
+                    _ => {}
+                }
             }
         }
+        self.parse_datatypes();
+
+        self.parse_functions(fun);
+
+        self.infer_num_types(fun);
+
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
 
         // Turns all the parsed functions into Bend functional representation
         for def in &self.definitions {
-            let fun_def = def.clone().to_fun(false).unwrap();
+            // Constructors given their fields by name are put in order first.
+            let mut def = def.clone();
+            def.order_kwargs(&self.book).map_err(PyTypeError::new_err)?;
+
+            let fun_def = def.to_fun(false).unwrap();
             self.book.defs.insert(fun_def.name.clone(), fun_def.clone());
         }
 
-        let main_def = self.parse_main(fun, py_args).unwrap();
+        let main_def = self.parse_main(fun);
+
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
 
         self.book
             .defs
@@ -889,13 +2642,227 @@ impl Parser {
 
         self.book.entrypoint = None;
 
-        println!("BEND:\n {}", self.book.display_pretty());
-
         let return_val = run(&self.book);
 
         match return_val {
-            Some(val) => val.0.to_string(),
+            Some(val) => Ok(val.0),
             None => panic!("Could not run Bend code."),
         }
     }
 }
+
+// The values a function body returns, in every branch.
+fn returned_exprs(body: &Stmt) -> Vec<&Expr> {
+    let mut exprs = vec![];
+    let mut stmts = vec![body];
+
+    while let Some(stmt) = stmts.pop() {
+        if let Stmt::Return { term } = stmt {
+            exprs.push(term.as_ref());
+        }
+        stmts.extend(stmt_children(stmt));
+    }
+
+    exprs
+}
+
+// Why a global can't be given to Bend, if it is a module or a function that
+// is not parsed, like a lambda, a builtin or one made at runtime.
+// The error of a global that can't be given to Bend keeps the type of the
+// original one, like an `OverflowError` for an int that doesn't fit.
+fn global_error(name: &Name, err: PyErr) -> PyErr {
+    Python::with_gil(|py| {
+        PyErr::from_type_bound(
+            err.get_type_bound(py),
+            format!(
+                "The global '{}' can't be given to Bend: {}",
+                unescape(name),
+                err.value_bound(py)
+            ),
+        )
+    })
+}
+
+fn unsupported_global(value: &Bound<'_, PyAny>) -> Option<&'static str> {
+    if value.is_instance_of::<PyModule>() {
+        Some("it is a module, only its functions can be used")
+    } else if value.is_callable() {
+        Some("only the functions defined with 'def' in the package can be called")
+    } else {
+        None
+    }
+}
+
+// Renames a variable, like the class given to a `@classmethod` to the class.
+struct Renamer {
+    from: Identifier,
+    to: Identifier,
+}
+
+impl Fold<TextRange> for Renamer {
+    type TargetU = TextRange;
+    type Error = std::convert::Infallible;
+    type UserContext = ();
+
+    fn will_map_user(&mut self, _: &TextRange) {}
+
+    fn map_user(&mut self, user: TextRange, _: ()) -> Result<TextRange, Self::Error> {
+        Ok(user)
+    }
+
+    fn fold_expr_name(&mut self, mut node: ExprName) -> Result<ExprName, Self::Error> {
+        if node.id == self.from {
+            node.id = self.to.clone();
+        }
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bend::fun::{Num, Term};
+    use pyo3::{prelude::*, types::PyDict};
+    use rustpython_parser::{ast::Mod, parse, Mode};
+
+    use super::{modules::load_modules, Parser};
+    use crate::types::{extract_type, modules::set_main_module, overflow::Overflow};
+
+    // Runs the function of the module with the Python values of `args`.
+    fn run(code: &str, fun: &str, args: &str) -> Term {
+        pyo3::prepare_freethreaded_python();
+
+        Python::with_gil(|py| {
+            let globals = PyDict::new_bound(py);
+            globals.set_item("__name__", "__main__").unwrap();
+            py.run_bound(code, Some(&globals), None).unwrap();
+            set_main_module(py, "__main__");
+
+            let args = py.eval_bound(args, Some(&globals), None).unwrap();
+            let params = py
+                .eval_bound(
+                    &format!("{}.__code__.co_varnames", fun),
+                    Some(&globals),
+                    None,
+                )
+                .unwrap();
+            let fun_args = args
+                .iter()
+                .unwrap()
+                .zip(params.iter().unwrap())
+                .map(|(arg, param)| {
+                    (
+                        param.unwrap().to_string(),
+                        extract_type(arg.unwrap()).unwrap(),
+                    )
+                })
+                .collect();
+
+            let Ok(Mod::Module(module)) = parse(code, Mode::Module, "main.py") else {
+                panic!("Could not parse the module");
+            };
+            let modules = load_modules(py, module.body, &globals).unwrap();
+            let mut parser = Parser::new(modules, fun_args, vec![], false, Overflow::Wrap);
+            parser.parse(fun).unwrap()
+        })
+    }
+
+    #[test]
+    fn optional_fields_end_with_none() {
+        let code = r#"
+from dataclasses import dataclass
+from typing import Optional
+
+@dataclass
+class LNode:
+    val: int
+    next: Optional["LNode"]
+
+def llen(l: Optional[LNode]) -> int:
+    if l is None:
+        return 0
+    else:
+        return 1 + llen(l.next)
+
+def llen_match(l: Optional[LNode]) -> int:
+    match l:
+        case LNode(val, next):
+            return 1 + llen_match(next)
+        case None:
+            return 0
+"#;
+        let args = "(LNode(1, LNode(2, None)),)";
+        assert_eq!(run(code, "llen", args), Term::Num { val: Num::I24(2) });
+        assert_eq!(
+            run(code, "llen_match", args),
+            Term::Num { val: Num::I24(2) }
+        );
+    }
+
+    #[test]
+    fn classes_can_be_part_of_many_unions() {
+        let code = r#"
+from dataclasses import dataclass
+
+@dataclass
+class Circle:
+    r: int
+
+@dataclass
+class Square:
+    s: int
+
+@dataclass
+class Ball:
+    d: int
+
+Shape = Circle | Square
+Round = Circle | Ball
+
+def area(x: Shape) -> int:
+    match x:
+        case Circle(r):
+            return 3 * r * r
+        case Square(s):
+            return s * s
+
+def size(x: Round) -> int:
+    match x:
+        case Circle(r):
+            return 2 * r
+        case Ball(d):
+            return d
+
+def both(n: int) -> int:
+    return area(Circle(n)) + size(Circle(n)) + size(Ball(n))
+"#;
+        assert_eq!(run(code, "both", "(2,)"), Term::Num { val: Num::I24(18) });
+    }
+
+    #[test]
+    fn classmethods_are_given_the_class() {
+        let code = r#"
+from dataclasses import dataclass
+
+@dataclass
+class Point:
+    x: int
+    y: int
+
+    @classmethod
+    def diag(cls, n: int) -> "Point":
+        return cls.shift(cls(0, 0), n)
+
+    @staticmethod
+    def shift(p: "Point", n: int) -> "Point":
+        return Point(p.x + n, p.y + n)
+
+    def norm1(self) -> int:
+        return self.x + self.y
+
+def f(n: int) -> int:
+    p = Point.diag(n)
+    return p.norm1() + p.shift(p, 1).norm1()
+"#;
+        assert_eq!(run(code, "f", "(3,)"), Term::Num { val: Num::I24(14) });
+    }
+}
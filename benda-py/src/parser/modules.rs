@@ -0,0 +1,251 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use pyo3::{
+    exceptions::PySyntaxError,
+    prelude::*,
+    types::{PyDict, PyModule},
+};
+use rustpython_parser::{
+    ast::{Expr as rExpr, Mod, Stmt as rStmt},
+    parse, Mode,
+};
+
+use crate::types::modules::prefix;
+
+// A module whose functions and classes can be given to Bend. The module of
+// the annotated function keeps its names, the others are prefixed with
+// their module, like `shapes/Circle`.
+pub struct Module {
+    pub prefix: String,
+    pub statements: Vec<rStmt>,
+    // The `__dict__` of the module, where its constants are.
+    pub globals: Py<PyDict>,
+    // The names assigned at the top of the module.
+    pub defined: HashSet<String>,
+    // The names imported from the other modules of the package, with the
+    // index of their module and their name there, or None for a module.
+    pub imports: HashMap<String, (usize, Option<String>)>,
+}
+
+struct Loader<'py> {
+    py: Python<'py>,
+    sys_modules: Bound<'py, PyDict>,
+    // The directory of the annotated function's file, or of its top package.
+    // Only the modules inside it are parsed, the standard library and the
+    // installed packages are left to Python.
+    root: Option<PathBuf>,
+    modules: Vec<Module>,
+    names: HashMap<String, usize>,
+}
+
+// Loads the module of the annotated function and every module of the
+// user's package it imports, directly or not.
+pub fn load_modules(
+    py: Python<'_>,
+    statements: Vec<rStmt>,
+    globals: &Bound<'_, PyDict>,
+) -> PyResult<Vec<Module>> {
+    let name = match globals.get_item("__name__")? {
+        Some(name) => name.to_string(),
+        None => String::new(),
+    };
+    let root = match globals.get_item("__file__")? {
+        Some(file) => Path::new(&file.to_string())
+            .canonicalize()
+            .ok()
+            .and_then(|file| package_root(&file)),
+        None => None,
+    };
+
+    let sys_modules = py
+        .import_bound("sys")?
+        .getattr("modules")?
+        .downcast_into::<PyDict>()?;
+
+    let mut loader = Loader {
+        py,
+        sys_modules,
+        root,
+        modules: vec![],
+        names: HashMap::new(),
+    };
+    loader.add(name, statements, globals.clone());
+
+    let mut index = 0;
+    while index < loader.modules.len() {
+        loader.follow_imports(index)?;
+        index += 1;
+    }
+
+    Ok(loader.modules)
+}
+
+impl<'py> Loader<'py> {
+    fn add(&mut self, name: String, statements: Vec<rStmt>, globals: Bound<'py, PyDict>) -> usize {
+        let defined = statements.iter().flat_map(defined_names).collect();
+
+        self.names.insert(name.clone(), self.modules.len());
+        self.modules.push(Module {
+            prefix: prefix(self.py, &name),
+            statements,
+            globals: globals.unbind(),
+            defined,
+            imports: HashMap::new(),
+        });
+        self.modules.len() - 1
+    }
+
+    // The index of an imported module, parsing it first if it is part of the
+    // package. Python imported it already, so it is in `sys.modules`.
+    fn load(&mut self, name: &str) -> PyResult<Option<usize>> {
+        if let Some(index) = self.names.get(name) {
+            return Ok(Some(*index));
+        }
+
+        let Some(module) = self.sys_modules.get_item(name)? else {
+            return Ok(None);
+        };
+        let Ok(module) = module.downcast_into::<PyModule>() else {
+            return Ok(None);
+        };
+        let Some(file) = self.package_file(&module) else {
+            return Ok(None);
+        };
+
+        let code = std::fs::read_to_string(&file)?;
+        let statements = match parse(&code, Mode::Module, &file.to_string_lossy()) {
+            Ok(Mod::Module(module)) => module.body,
+            Ok(_) => return Ok(None),
+            Err(err) => return Err(PySyntaxError::new_err(err.to_string())),
+        };
+
+        Ok(Some(self.add(name.to_string(), statements, module.dict())))
+    }
+
+    fn package_file(&self, module: &Bound<'py, PyModule>) -> Option<PathBuf> {
+        let root = self.root.as_ref()?;
+        let file = module.getattr("__file__").ok()?.extract::<String>().ok()?;
+        let file = Path::new(&file).canonicalize().ok()?;
+
+        let in_package = file.starts_with(root)
+            && file.extension().is_some_and(|ext| ext == "py")
+            && !file.components().any(|part| {
+                let part = part.as_os_str();
+                part == "site-packages" || part == "dist-packages"
+            });
+        in_package.then_some(file)
+    }
+
+    fn follow_imports(&mut self, index: usize) -> PyResult<()> {
+        let statements = self.modules[index].statements.clone();
+
+        for stmt in &statements {
+            match stmt {
+                // `import shapes` and `import pkg.shapes as s`. The module of
+                // `import pkg.shapes` is used as `pkg.shapes`.
+                rStmt::Import(import) => {
+                    for alias in &import.names {
+                        if let Some(module) = self.load(alias.name.as_str())? {
+                            let local = match &alias.asname {
+                                Some(asname) => asname.to_string(),
+                                None => alias.name.to_string(),
+                            };
+                            self.modules[index].imports.insert(local, (module, None));
+                        }
+                    }
+                }
+                // `from shapes import Circle`, `from . import shapes` and
+                // `from .shapes import Circle`.
+                rStmt::ImportFrom(import) => {
+                    let Some(from) = self.resolve_module(index, import)? else {
+                        continue;
+                    };
+
+                    for alias in &import.names {
+                        let local = match &alias.asname {
+                            Some(asname) => asname.to_string(),
+                            None => alias.name.to_string(),
+                        };
+
+                        let submodule = format!("{}.{}", from, alias.name);
+                        let import = match self.load(&submodule)? {
+                            Some(module) => Some((module, None)),
+                            None => self
+                                .load(&from)?
+                                .map(|module| (module, Some(alias.name.to_string()))),
+                        };
+
+                        if let Some(import) = import {
+                            self.modules[index].imports.insert(local, import);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // The absolute name of the module of a `from ... import`, resolving the
+    // relative ones against the package of the importing module.
+    fn resolve_module(
+        &self,
+        index: usize,
+        import: &rustpython_parser::ast::StmtImportFrom,
+    ) -> PyResult<Option<String>> {
+        let level = import.level.map_or(0, |level| level.to_usize());
+        let module = import.module.as_ref().map_or("", |module| module.as_str());
+
+        if level == 0 {
+            return Ok(Some(module.to_string()));
+        }
+
+        let globals = self.modules[index].globals.bind(self.py);
+        let Some(package) = globals.get_item("__package__")? else {
+            return Ok(None);
+        };
+        if package.is_none() {
+            return Ok(None);
+        }
+
+        let name = format!("{}{}", ".".repeat(level), module);
+        let resolved = self
+            .py
+            .import_bound("importlib.util")?
+            .call_method1("resolve_name", (name, package));
+
+        Ok(resolved.ok().map(|name| name.to_string()))
+    }
+}
+
+// The directory containing the top package of a file, or its own directory
+// if it is not in a package.
+fn package_root(file: &Path) -> Option<PathBuf> {
+    let mut root = file.parent()?;
+    while root.join("__init__.py").exists() {
+        root = root.parent()?;
+    }
+    Some(root.to_path_buf())
+}
+
+fn defined_names(stmt: &rStmt) -> Vec<String> {
+    match stmt {
+        rStmt::FunctionDef(fun_def) => vec![fun_def.name.to_string()],
+        rStmt::ClassDef(class) => vec![class.name.to_string()],
+        rStmt::Assign(assign) => assign.targets.iter().filter_map(target_name).collect(),
+        rStmt::AnnAssign(assign) => target_name(&assign.target).into_iter().collect(),
+        rStmt::TypeAlias(alias) => target_name(&alias.name).into_iter().collect(),
+        _ => vec![],
+    }
+}
+
+fn target_name(target: &rExpr) -> Option<String> {
+    match target {
+        rExpr::Name(name) => Some(name.id.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,320 @@
+use bend::{
+    fun::{parser::Indent, Op},
+    imp::{self, parser::PyParser, Expr},
+};
+
+use super::num_types::NumType;
+
+// The Bend functions for the operations HVM lacks on 24-bit numbers. HVM
+// compiles `**` on ints as a xor, has no shifts for i24 and rounds divisions
+// towards zero, so they are computed from the operations it has, giving the
+// same results as the number classes of benda.
+const DEFS: [&str; 10] = [
+    "benda/u24_pow(a, b):
+  if b == 0:
+    return 1
+  else:
+    half = benda/u24_pow(a * a, b / 2)
+    if b % 2 == 0:
+      return half
+    else:
+      return half * a
+",
+    // Negative exponents give the truncated result, which is only not zero
+    // for 1 and -1.
+    "benda/i24_pow(a, b):
+  if b < +0:
+    if a == +1:
+      return +1
+    elif a == -1:
+      if b % +2 == +0:
+        return +1
+      else:
+        return -1
+    else:
+      return +0
+  else:
+    return benda/i24_pow_nat(a, b)
+",
+    "benda/i24_pow_nat(a, b):
+  if b == +0:
+    return +1
+  else:
+    half = benda/i24_pow_nat(a * a, b / +2)
+    if b % +2 == +0:
+      return half
+    else:
+      return half * a
+",
+    // Like the shifts of the two's complement, only the lower 5 bits of the
+    // shift are used and right shifts keep the sign.
+    "benda/i24_shl(a, b):
+  return a * benda/i24_pow_nat(+2, b & +31)
+",
+    "benda/i24_shr(a, b):
+  if (b & +31) == +0:
+    return a
+  else:
+    half = a / +2
+    if a % +2 < +0:
+      return benda/i24_shr(half - +1, (b & +31) - +1)
+    else:
+      return benda/i24_shr(half, (b & +31) - +1)
+",
+    "benda/i24_floordiv(a, b):
+  q = a / b
+  if (a % b != +0) & ((a < +0) != (b < +0)):
+    return q - +1
+  else:
+    return q
+",
+    "benda/i24_mod(a, b):
+  r = a % b
+  if (r != +0) & ((r < +0) != (b < +0)):
+    return r + b
+  else:
+    return r
+",
+    "benda/f24_floordiv(a, b):
+  return benda/f24_floor(a / b)
+",
+    // The fractional part of infinity is NaN, and infinity is its own floor.
+    "benda/f24_floor(x):
+  r = x % 1.0
+  if r == r:
+    if r < 0.0:
+      return x - r - 1.0
+    else:
+      return x - r
+  else:
+    return x
+",
+    "benda/f24_mod(a, b):
+  r = a % b
+  if (r != 0.0) & ((r < 0.0) != (b < 0.0)):
+    return r + b
+  else:
+    return r
+",
+];
+
+// Floor division and modulo, written by the parser as calls to these
+// functions until the type of their operands is known.
+pub const FLOOR_DIV: &str = "benda/floordiv";
+pub const MOD: &str = "benda/mod";
+
+// The operation a call to `FLOOR_DIV` or `MOD` stands for.
+pub fn floor_op(fun: &Expr) -> Option<Op> {
+    match fun {
+        Expr::Var { nam } if nam == FLOOR_DIV => Some(Op::DIV),
+        Expr::Var { nam } if nam == MOD => Some(Op::REM),
+        _ => None,
+    }
+}
+
+pub fn definitions() -> Vec<imp::Definition> {
+    DEFS.iter()
+        .map(|code| PyParser::new(code).parse_def(Indent::Val(0)).unwrap().0)
+        .collect()
+}
+
+// The function of the library computing the operation on the type, when
+// HVM doesn't have it. Unsigned numbers are already rounded down by HVM.
+pub fn function(op: Op, floor: bool, typ: NumType) -> Option<&'static str> {
+    match (op, typ) {
+        (Op::DIV, NumType::I24) if floor => Some("benda/i24_floordiv"),
+        (Op::REM, NumType::I24) if floor => Some("benda/i24_mod"),
+        (Op::DIV, NumType::F24) if floor => Some("benda/f24_floordiv"),
+        (Op::REM, NumType::F24) if floor => Some("benda/f24_mod"),
+        (Op::POW, NumType::U24) => Some("benda/u24_pow"),
+        (Op::POW, NumType::I24) => Some("benda/i24_pow"),
+        (Op::SHL, NumType::I24) => Some("benda/i24_shl"),
+        (Op::SHR, NumType::I24) => Some("benda/i24_shr"),
+        // On floats these are atan2, log and pow in HVM.
+        (Op::AND | Op::OR | Op::XOR | Op::SHL | Op::SHR, NumType::F24) => {
+            panic!("The operation {} is not supported on floats", op)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bend::{
+        fun::{Book, Name, Num, Term},
+        imp::{Definition, Expr, Stmt},
+    };
+    use pyo3::prelude::*;
+    use rustpython_parser::ast::Operator;
+
+    use super::super::{num_types::NumTypes, Parser};
+    use crate::{
+        benda_ffi,
+        types::{f24::f24, i24::i24, overflow::Overflow, u24::u24},
+    };
+
+    const INT_OPS: [(&str, Operator); 12] = [
+        ("__add__", Operator::Add),
+        ("__sub__", Operator::Sub),
+        ("__mul__", Operator::Mult),
+        ("__truediv__", Operator::Div),
+        ("__floordiv__", Operator::FloorDiv),
+        ("__mod__", Operator::Mod),
+        ("__pow__", Operator::Pow),
+        ("__lshift__", Operator::LShift),
+        ("__rshift__", Operator::RShift),
+        ("__and__", Operator::BitAnd),
+        ("__or__", Operator::BitOr),
+        ("__xor__", Operator::BitXor),
+    ];
+
+    const FLOAT_OPS: [(&str, Operator); 7] = [
+        ("__add__", Operator::Add),
+        ("__sub__", Operator::Sub),
+        ("__mul__", Operator::Mult),
+        ("__truediv__", Operator::Div),
+        ("__floordiv__", Operator::FloorDiv),
+        ("__mod__", Operator::Mod),
+        ("__pow__", Operator::Pow),
+    ];
+
+    fn to_py(py: Python<'_>, num: Num) -> PyObject {
+        match num {
+            Num::U24(val) => u24::new(val).into_py(py),
+            Num::I24(val) => i24::new(val).into_py(py),
+            Num::F24(val) => f24::new(val).into_py(py),
+        }
+    }
+
+    fn from_py(val: &Bound<'_, PyAny>) -> Num {
+        if let Ok(val) = val.extract::<u24>() {
+            return Num::U24(val.get());
+        }
+        if let Ok(val) = val.extract::<i24>() {
+            return Num::I24(val.get());
+        }
+        Num::F24(val.extract::<f24>().unwrap().get())
+    }
+
+    // The result of the operation with the classes of benda, if it has one.
+    fn python_result(method: &str, a: Num, b: Num) -> Option<Num> {
+        Python::with_gil(|py| {
+            let a = to_py(py, a);
+            let res = a.bind(py).call_method1(method, (to_py(py, b),)).ok()?;
+            Some(from_py(&res))
+        })
+    }
+
+    // Runs every operation on HVM at once, after the rewrites of the parser.
+    fn hvm_results(cases: &[(Operator, Num, Num)]) -> Vec<Num> {
+        let els = cases
+            .iter()
+            .map(|(op, a, b)| Parser::operation(op, Expr::Num { val: *a }, Expr::Num { val: *b }))
+            .collect();
+
+        let mut defs = vec![Definition {
+            name: Name::new("main"),
+            params: vec![],
+            body: Stmt::Return {
+                term: Box::new(Expr::Tup { els }),
+            },
+        }];
+        NumTypes::new(HashMap::new(), HashMap::new(), false, Overflow::Wrap)
+            .infer(&mut defs)
+            .unwrap();
+
+        let mut book = Book::builtins();
+        for def in defs {
+            let is_main = def.name == "main";
+            let def = def.to_fun(is_main).unwrap();
+            book.defs.insert(def.name.clone(), def);
+        }
+        book.entrypoint = None;
+
+        let mut results = vec![];
+        let (term, _, _) = benda_ffi::run(&book).unwrap();
+        let mut terms = vec![&term];
+
+        // The tuple is read back as nested pairs.
+        while let Some(term) = terms.pop() {
+            match term {
+                Term::Fan { els, .. } => terms.extend(els.iter().rev()),
+                Term::Num { val } => results.push(*val),
+                term => panic!("'{}' is not a number", term),
+            }
+        }
+
+        results
+    }
+
+    fn same(a: Num, b: Num) -> bool {
+        match (a, b) {
+            (Num::F24(a), Num::F24(b)) => a == b || (a.is_nan() && b.is_nan()),
+            (a, b) => a == b,
+        }
+    }
+
+    // Operations that raise in Python, like a division by zero, are left out
+    // since HVM can't raise.
+    fn check(ops: &[(&str, Operator)], vals: &[Num]) {
+        pyo3::prepare_freethreaded_python();
+
+        let mut cases = vec![];
+        let mut expected = vec![];
+        for (method, op) in ops {
+            for &a in vals {
+                for &b in vals {
+                    if let Some(res) = python_result(method, a, b) {
+                        cases.push((*op, a, b));
+                        expected.push(res);
+                    }
+                }
+            }
+        }
+
+        for ((case, expected), got) in cases.iter().zip(expected).zip(hvm_results(&cases)) {
+            let (op, a, b) = case;
+            assert!(
+                same(expected, got),
+                "{:?} {:?} {:?}: benda gives {:?}, HVM gives {:?}",
+                a,
+                op,
+                b,
+                expected,
+                got
+            );
+        }
+    }
+
+    #[test]
+    fn floor_division_rounds_down() {
+        let cases = [
+            (Operator::FloorDiv, Num::I24(-7), Num::I24(2)),
+            (Operator::Mod, Num::I24(-7), Num::I24(2)),
+            (Operator::FloorDiv, Num::F24(7.0), Num::F24(2.0)),
+            (Operator::Mod, Num::F24(-7.0), Num::F24(2.0)),
+        ];
+        let expected = [Num::I24(-4), Num::I24(1), Num::F24(3.0), Num::F24(1.0)];
+        assert_eq!(hvm_results(&cases), expected);
+    }
+
+    #[test]
+    fn u24_operations() {
+        let vals = [0, 1, 2, 3, 7, 1000, 0xffffff].map(Num::U24);
+        check(&INT_OPS, &vals);
+    }
+
+    #[test]
+    fn i24_operations() {
+        let vals = [0, 1, -1, 2, -7, 5, 100, -8388608, 8388607].map(Num::I24);
+        check(&INT_OPS, &vals);
+    }
+
+    #[test]
+    fn f24_operations() {
+        let vals = [0.0, 1.5, -2.25, 3.0, -7.0, 1000.5].map(Num::F24);
+        check(&FLOAT_OPS, &vals);
+    }
+}
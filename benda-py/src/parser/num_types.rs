@@ -0,0 +1,680 @@
+// `Name` is only mutable inside its string interner, so it is a fine key.
+#![allow(clippy::mutable_key_type)]
+
+use std::collections::HashMap;
+
+use bend::{
+    fun::{parser::Indent, Name, Num, Op},
+    imp::{self, parser::PyParser, AssignPattern, Expr, Stmt},
+};
+use num_bigint::BigInt;
+use pyo3::{exceptions::PyTypeError, PyErr, PyResult};
+use rustpython_parser::ast::Expr as rExpr;
+
+use super::{annotations, bignum, num_ops};
+use crate::types::{
+    bignum::literal,
+    i24::i24,
+    overflow::{check_literal, Overflow},
+};
+
+// The numeric types of HVM. Python ints are signed, so they are `I24`,
+// or bignums when they were enabled with `@bjit(bignum=True)`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NumType {
+    U24,
+    I24,
+    F24,
+    Big,
+}
+
+impl NumType {
+    // Reads an annotation like `x: benda.i24`, `x: u24` or `x: float`.
+    pub fn from_annotation(annotation: &rExpr, bignum: bool) -> Option<Self> {
+        // `Optional[int]` holds an int when it isn't None.
+        let annotation = annotations::optional_type(annotation)?;
+        let name = match &annotation {
+            rExpr::Name(name) => name.id.to_string(),
+            rExpr::Attribute(att) => match att.value.as_ref() {
+                rExpr::Name(module) if module.id.as_str() == "benda" => att.attr.to_string(),
+                _ => return None,
+            },
+            rExpr::Constant(c) => c.value.as_str()?.to_string(),
+            _ => return None,
+        };
+
+        match name.as_str() {
+            "u24" | "benda.u24" => Some(NumType::U24),
+            "int" if bignum => Some(NumType::Big),
+            "i24" | "benda.i24" | "int" => Some(NumType::I24),
+            "f24" | "benda.f24" | "float" => Some(NumType::F24),
+            _ => None,
+        }
+    }
+
+    pub fn from_num(num: &Num) -> Self {
+        match num {
+            Num::U24(_) => NumType::U24,
+            Num::I24(_) => NumType::I24,
+            Num::F24(_) => NumType::F24,
+        }
+    }
+
+    // The type of an operation on both types, promoting ints to floats
+    // and unsigned to signed numbers like Python does.
+    fn join(a: Option<Self>, b: Option<Self>) -> Option<Self> {
+        match (a, b) {
+            (None, t) | (t, None) => t,
+            (Some(NumType::F24), _) | (_, Some(NumType::F24)) => Some(NumType::F24),
+            (Some(NumType::Big), _) | (_, Some(NumType::Big)) => Some(NumType::Big),
+            (Some(NumType::I24), _) | (_, Some(NumType::I24)) => Some(NumType::I24),
+            _ => Some(NumType::U24),
+        }
+    }
+}
+
+// The numeric types of the parameters and of the result of a function.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Signature {
+    pub params: Vec<Option<NumType>>,
+    pub ret: Option<NumType>,
+}
+
+const U24_TO_F24: &str = "benda/u24_to_f24";
+const I24_TO_F24: &str = "benda/i24_to_f24";
+
+// HVM can't convert between numeric types, so ints are converted to floats
+// by adding their bits one by one.
+const TO_F24_DEFS: [&str; 2] = [
+    "benda/u24_to_f24(n):
+  if n == 0:
+    return 0.0
+  else:
+    half = benda/u24_to_f24(n / 2) * 2.0
+    if n % 2 == 0:
+      return half
+    else:
+      return half + 1.0
+",
+    "benda/i24_to_f24(n):
+  if n == +0:
+    return 0.0
+  else:
+    half = benda/i24_to_f24(n / +2) * 2.0
+    if n % +2 == +0:
+      return half
+    else:
+      if n < +0:
+        return half - 1.0
+      else:
+        return half + 1.0
+",
+];
+
+pub fn call(fun: &str, args: Vec<Expr>) -> Expr {
+    Expr::Call {
+        fun: Box::new(Expr::Var {
+            nam: Name::new(fun),
+        }),
+        args,
+        kwargs: vec![],
+    }
+}
+
+fn is_cmp(op: &Op) -> bool {
+    matches!(op, Op::EQ | Op::NEQ | Op::LT | Op::GT)
+}
+
+// Infers the numeric type of the variables of each function, to give the
+// right type to the number literals and to convert ints used as floats.
+//
+// Number literals have no type of their own and take the type of the values
+// they are used with. The types of the parameters come from annotations and
+// from the values passed to the annotated function, and flow from the
+// arguments of calls to the parameters of the called functions.
+pub struct NumTypes {
+    sigs: HashMap<Name, Signature>,
+    fields: HashMap<Name, Vec<(Name, Option<NumType>)>>,
+    vars: HashMap<Name, HashMap<Name, NumType>>,
+    uses_conversion: bool,
+    bignum: bool,
+    uses_bignum: bool,
+    uses_num_ops: bool,
+    overflow: Overflow,
+    // The first literal that doesn't fit, when the policy is an error.
+    error: Option<PyErr>,
+}
+
+impl NumTypes {
+    pub fn new(
+        sigs: HashMap<Name, Signature>,
+        fields: HashMap<Name, Vec<(Name, Option<NumType>)>>,
+        bignum: bool,
+        overflow: Overflow,
+    ) -> Self {
+        Self {
+            sigs,
+            fields,
+            vars: HashMap::new(),
+            uses_conversion: false,
+            bignum,
+            uses_bignum: false,
+            uses_num_ops: false,
+            overflow,
+            error: None,
+        }
+    }
+
+    pub fn into_signatures(self) -> HashMap<Name, Signature> {
+        self.sigs
+    }
+
+    pub fn infer(&mut self, defs: &mut Vec<imp::Definition>) -> PyResult<()> {
+        // Types only become more general, so this ends after a few rounds.
+        loop {
+            let before = (self.sigs.clone(), self.vars.clone());
+
+            for def in defs.iter() {
+                let sig = self.sigs.entry(def.name.clone()).or_default();
+                sig.params.resize(def.params.len(), None);
+
+                let mut env = self.vars.remove(&def.name).unwrap_or_default();
+                for (param, typ) in def.params.iter().zip(sig.params.clone()) {
+                    if let Some(typ) = typ {
+                        env.insert(param.clone(), typ);
+                    }
+                }
+
+                self.collect_stmt(&def.name, &def.body, &mut env);
+                self.vars.insert(def.name.clone(), env);
+            }
+
+            if before == (self.sigs.clone(), self.vars.clone()) {
+                break;
+            }
+        }
+
+        for def in defs.iter_mut() {
+            let env = self.vars.remove(&def.name).unwrap_or_default();
+            let ret = self.sigs.get(&def.name).and_then(|sig| sig.ret);
+            self.rewrite_stmt(&mut def.body, &env, ret);
+        }
+
+        if self.uses_conversion {
+            for code in TO_F24_DEFS {
+                let (def, _) = PyParser::new(code).parse_def(Indent::Val(0)).unwrap();
+                defs.push(def);
+            }
+        }
+
+        if self.uses_bignum {
+            defs.extend(bignum::definitions());
+        }
+
+        if self.uses_num_ops {
+            defs.extend(num_ops::definitions());
+        }
+
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    fn expr_type(&self, expr: &Expr, env: &HashMap<Name, NumType>) -> Option<NumType> {
+        match expr {
+            Expr::Num { val: Num::F24(_) } => Some(NumType::F24),
+            // Lists are only made by the parser for bignum literals.
+            Expr::Lst { .. } if self.bignum => Some(NumType::Big),
+            Expr::Var { nam } => env.get(nam).copied(),
+            Expr::Opr { op, .. } if is_cmp(op) => Some(NumType::U24),
+            Expr::Opr { lhs, rhs, .. } => {
+                NumType::join(self.expr_type(lhs, env), self.expr_type(rhs, env))
+            }
+            Expr::Call { fun, args, .. } if num_ops::floor_op(fun).is_some() => {
+                let types = args.iter().map(|arg| self.expr_type(arg, env));
+                types.fold(None, NumType::join)
+            }
+            Expr::Call { fun, .. } => match fun.as_ref() {
+                Expr::Var { nam } => self.sigs.get(nam).and_then(|sig| sig.ret),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn set_var(env: &mut HashMap<Name, NumType>, nam: &Name, typ: Option<NumType>) {
+        if let Some(typ) = NumType::join(env.get(nam).copied(), typ) {
+            env.insert(nam.clone(), typ);
+        }
+    }
+
+    fn collect_expr(&mut self, expr: &Expr, env: &mut HashMap<Name, NumType>) {
+        match expr {
+            Expr::Opr { lhs, rhs, .. } => self.collect_operands(lhs, rhs, env),
+            Expr::Call { fun, args, .. } if num_ops::floor_op(fun).is_some() => {
+                if let [lhs, rhs] = args.as_slice() {
+                    self.collect_operands(lhs, rhs, env);
+                }
+            }
+            Expr::Call { fun, args, kwargs } => {
+                for arg in args.iter().chain(kwargs.iter().map(|(_, arg)| arg)) {
+                    self.collect_expr(arg, env);
+                }
+
+                if let Expr::Var { nam } = fun.as_ref() {
+                    let types: Vec<_> = args.iter().map(|arg| self.expr_type(arg, env)).collect();
+
+                    if let Some(sig) = self.sigs.get_mut(nam) {
+                        for (param, typ) in sig.params.iter_mut().zip(types) {
+                            *param = NumType::join(*param, typ);
+                        }
+                    }
+                }
+            }
+            _ => {
+                for child in expr_children(expr) {
+                    self.collect_expr(child, env);
+                }
+            }
+        }
+    }
+
+    fn collect_operands(&mut self, lhs: &Expr, rhs: &Expr, env: &mut HashMap<Name, NumType>) {
+        self.collect_expr(lhs, env);
+        self.collect_expr(rhs, env);
+
+        // A variable without a type takes the type of the other operand.
+        let (lt, rt) = (self.expr_type(lhs, env), self.expr_type(rhs, env));
+        match (lhs, rhs) {
+            (Expr::Var { nam }, _) if lt.is_none() => Self::set_var(env, nam, rt),
+            (_, Expr::Var { nam }) if rt.is_none() => Self::set_var(env, nam, lt),
+            _ => {}
+        }
+    }
+
+    fn collect_stmt(&mut self, fun: &Name, stmt: &Stmt, env: &mut HashMap<Name, NumType>) {
+        match stmt {
+            Stmt::Assign { pat, val, nxt } => {
+                self.collect_expr(val, env);
+                if let AssignPattern::Var(nam) = pat {
+                    let typ = self.expr_type(val, env);
+                    Self::set_var(env, nam, typ);
+                }
+                if let Some(nxt) = nxt {
+                    self.collect_stmt(fun, nxt, env);
+                }
+            }
+            // The fields bound by a match have the types of their annotations.
+            Stmt::Match {
+                bnd: Some(bnd),
+                arms,
+                ..
+            } => {
+                for arm in arms {
+                    let fields = arm.lft.as_ref().and_then(|ctr| self.fields.get(ctr));
+                    for (field, typ) in fields.into_iter().flatten() {
+                        Self::set_var(env, &Name::new(format!("{}.{}", bnd, field)), *typ);
+                    }
+                }
+                for expr in stmt_exprs(stmt) {
+                    self.collect_expr(expr, env);
+                }
+                for child in stmt_children(stmt) {
+                    self.collect_stmt(fun, child, env);
+                }
+            }
+            // Dataclasses are opened to read their fields, and the datatype
+            // has the name of their constructor.
+            Stmt::Open { typ, var, nxt } => {
+                let fields = self.fields.get(typ).cloned().unwrap_or_default();
+                for (field, typ) in fields {
+                    Self::set_var(env, &Name::new(format!("{}.{}", var, field)), typ);
+                }
+                self.collect_stmt(fun, nxt, env);
+            }
+            Stmt::Return { term } => {
+                self.collect_expr(term, env);
+                let typ = self.expr_type(term, env);
+                let sig = self.sigs.entry(fun.clone()).or_default();
+                sig.ret = NumType::join(sig.ret, typ);
+            }
+            _ => {
+                for expr in stmt_exprs(stmt) {
+                    self.collect_expr(expr, env);
+                }
+                for child in stmt_children(stmt) {
+                    self.collect_stmt(fun, child, env);
+                }
+            }
+        }
+    }
+
+    // Gives the expected type to the literals of the expression, converting
+    // ints to floats when a float is expected. Operations on bignums become
+    // calls to their Bend library.
+    fn rewrite_expr(
+        &mut self,
+        expr: &mut Expr,
+        env: &HashMap<Name, NumType>,
+        expected: Option<NumType>,
+    ) {
+        let typ = self.expr_type(expr, env);
+
+        match expr {
+            Expr::Num { val } => {
+                match (*val, expected) {
+                    (Num::U24(v), Some(NumType::Big)) => *expr = literal(&BigInt::from(v)),
+                    (Num::I24(v), Some(NumType::Big)) => *expr = literal(&BigInt::from(v)),
+                    (Num::U24(v), Some(NumType::I24)) => {
+                        if v as i64 > i24::MAX {
+                            if let Err(err) = check_literal(self.overflow, &v.to_string()) {
+                                self.error.get_or_insert(err);
+                            }
+                        }
+                        *val = Num::I24(v as i32)
+                    }
+                    (Num::U24(v), Some(NumType::F24)) => *val = Num::F24(v as f32),
+                    (Num::I24(v), Some(NumType::F24)) => *val = Num::F24(v as f32),
+                    _ => {}
+                }
+                return;
+            }
+            Expr::Opr { op, lhs, rhs } => {
+                *expr = self.rewrite_operation(*op, false, lhs, rhs, env, expected);
+            }
+            Expr::Call { fun, args, .. } if num_ops::floor_op(fun).is_some() => {
+                let op = num_ops::floor_op(fun).unwrap();
+                if let [lhs, rhs] = args.as_mut_slice() {
+                    *expr = self.rewrite_operation(op, true, lhs, rhs, env, expected);
+                }
+            }
+            Expr::Call { fun, args, kwargs } => {
+                let params = match fun.as_ref() {
+                    Expr::Var { nam } => self.sigs.get(nam).map(|sig| sig.params.clone()),
+                    _ => None,
+                };
+                let params = params.unwrap_or_default();
+
+                for (i, arg) in args.iter_mut().enumerate() {
+                    self.rewrite_expr(arg, env, params.get(i).copied().flatten());
+                }
+                for (_, arg) in kwargs {
+                    self.rewrite_expr(arg, env, None);
+                }
+            }
+            Expr::Ctr { name, args, kwargs } => {
+                let fields = self.fields.get(name).cloned().unwrap_or_default();
+                let field_type = |nam: &Name| {
+                    let field = fields.iter().find(|(field, _)| field == nam);
+                    field.and_then(|(_, typ)| *typ)
+                };
+
+                for (i, arg) in args.iter_mut().enumerate() {
+                    let typ = fields.get(i).and_then(|(_, typ)| *typ);
+                    self.rewrite_expr(arg, env, typ);
+                }
+                for (nam, arg) in kwargs {
+                    self.rewrite_expr(arg, env, field_type(nam));
+                }
+            }
+            _ => {
+                for child in expr_children_mut(expr) {
+                    self.rewrite_expr(child, env, None);
+                }
+            }
+        }
+
+        let conversion = match (typ, expected) {
+            (Some(NumType::U24), Some(NumType::F24)) => U24_TO_F24,
+            (Some(NumType::I24), Some(NumType::F24)) => I24_TO_F24,
+            (Some(NumType::U24), Some(NumType::Big)) => bignum::FROM_U24,
+            (Some(NumType::Big), Some(NumType::F24)) => {
+                let err = PyTypeError::new_err("Bignums can't be converted to floats");
+                self.error.get_or_insert(err);
+                return;
+            }
+            (Some(NumType::I24), Some(NumType::Big)) => {
+                let err = PyTypeError::new_err("i24 values can't be converted to bignums");
+                self.error.get_or_insert(err);
+                return;
+            }
+            _ => return,
+        };
+
+        if expected == Some(NumType::Big) {
+            self.uses_bignum = true;
+        } else {
+            self.uses_conversion = true;
+        }
+
+        *expr = call(conversion, vec![std::mem::replace(expr, Expr::Era)]);
+    }
+
+    // Writes an operation with the operations of its type, which are calls
+    // to the bignum library for bignums and to functions computing it when
+    // HVM doesn't have it. `floor` is for the floor division and modulo.
+    fn rewrite_operation(
+        &mut self,
+        op: Op,
+        floor: bool,
+        lhs: &mut Expr,
+        rhs: &mut Expr,
+        env: &HashMap<Name, NumType>,
+        expected: Option<NumType>,
+    ) -> Expr {
+        let typ = NumType::join(self.expr_type(lhs, env), self.expr_type(rhs, env));
+        let typ = if is_cmp(&op) { typ } else { typ.or(expected) };
+
+        // An operation on int literals alone, like `-7 // 2`, is signed when
+        // one of them is negative.
+        let typ = typ.or_else(|| match (&*lhs, &*rhs) {
+            (Expr::Num { val: l }, Expr::Num { val: r }) => {
+                NumType::join(Some(NumType::from_num(l)), Some(NumType::from_num(r)))
+            }
+            _ => None,
+        });
+
+        self.rewrite_expr(lhs, env, typ);
+        self.rewrite_expr(rhs, env, typ);
+
+        let lhs = std::mem::replace(lhs, Expr::Era);
+        let rhs = std::mem::replace(rhs, Expr::Era);
+
+        if typ == Some(NumType::Big) {
+            self.uses_bignum = true;
+            return bignum::operation(op, lhs, rhs).unwrap_or_else(|err| {
+                self.error.get_or_insert(err);
+                Expr::Era
+            });
+        }
+
+        match typ.and_then(|typ| num_ops::function(op, floor, typ)) {
+            Some(fun) => {
+                self.uses_num_ops = true;
+                call(fun, vec![lhs, rhs])
+            }
+            None => Expr::Opr {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+        }
+    }
+
+    fn rewrite_stmt(
+        &mut self,
+        stmt: &mut Stmt,
+        env: &HashMap<Name, NumType>,
+        ret: Option<NumType>,
+    ) {
+        match stmt {
+            Stmt::Assign { pat, val, nxt } => {
+                let typ = match pat {
+                    AssignPattern::Var(nam) => env.get(nam).copied(),
+                    _ => None,
+                };
+                self.rewrite_expr(val, env, typ);
+                if let Some(nxt) = nxt {
+                    self.rewrite_stmt(nxt, env, ret);
+                }
+            }
+            Stmt::Return { term } => self.rewrite_expr(term, env, ret),
+            _ => {
+                for expr in stmt_exprs_mut(stmt) {
+                    self.rewrite_expr(expr, env, None);
+                }
+                for child in stmt_children_mut(stmt) {
+                    self.rewrite_stmt(child, env, ret);
+                }
+            }
+        }
+    }
+}
+
+pub(super) fn expr_children(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::Call { fun, args, kwargs } => std::iter::once(fun.as_ref())
+            .chain(args)
+            .chain(kwargs.iter().map(|(_, arg)| arg))
+            .collect(),
+        Expr::Lam { bod, .. } => vec![bod],
+        Expr::Opr { lhs, rhs, .. } => vec![lhs, rhs],
+        Expr::Lst { els } | Expr::Tup { els } | Expr::Sup { els } => els.iter().collect(),
+        Expr::Ctr { args, kwargs, .. } => args
+            .iter()
+            .chain(kwargs.iter().map(|(_, arg)| arg))
+            .collect(),
+        Expr::LstMap {
+            term, iter, cond, ..
+        } => [Some(term), Some(iter), cond.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|expr| expr.as_ref())
+            .collect(),
+        Expr::Map { entries } => entries.iter().flat_map(|(k, v)| [k, v]).collect(),
+        Expr::MapGet { key, .. } => vec![key],
+        Expr::TreeNode { left, right } => vec![left, right],
+        Expr::TreeLeaf { val } => vec![val],
+        Expr::Era | Expr::Var { .. } | Expr::Chn { .. } | Expr::Num { .. } | Expr::Str { .. } => {
+            vec![]
+        }
+    }
+}
+
+fn expr_children_mut(expr: &mut Expr) -> Vec<&mut Expr> {
+    match expr {
+        Expr::Call { fun, args, kwargs } => std::iter::once(fun.as_mut())
+            .chain(args)
+            .chain(kwargs.iter_mut().map(|(_, arg)| arg))
+            .collect(),
+        Expr::Lam { bod, .. } => vec![bod],
+        Expr::Opr { lhs, rhs, .. } => vec![lhs, rhs],
+        Expr::Lst { els } | Expr::Tup { els } | Expr::Sup { els } => els.iter_mut().collect(),
+        Expr::Ctr { args, kwargs, .. } => args
+            .iter_mut()
+            .chain(kwargs.iter_mut().map(|(_, arg)| arg))
+            .collect(),
+        Expr::LstMap {
+            term, iter, cond, ..
+        } => [Some(term), Some(iter), cond.as_mut()]
+            .into_iter()
+            .flatten()
+            .map(|expr| expr.as_mut())
+            .collect(),
+        Expr::Map { entries } => entries.iter_mut().flat_map(|(k, v)| [k, v]).collect(),
+        Expr::MapGet { key, .. } => vec![key],
+        Expr::TreeNode { left, right } => vec![left, right],
+        Expr::TreeLeaf { val } => vec![val],
+        Expr::Era | Expr::Var { .. } | Expr::Chn { .. } | Expr::Num { .. } | Expr::Str { .. } => {
+            vec![]
+        }
+    }
+}
+
+// The expressions directly inside a statement, without its nested statements.
+pub(super) fn stmt_exprs(stmt: &Stmt) -> Vec<&Expr> {
+    match stmt {
+        Stmt::Assign { val, .. } | Stmt::InPlace { val, .. } | Stmt::Ask { val, .. } => vec![val],
+        Stmt::Use { val, .. } => vec![val],
+        Stmt::If { cond, .. } => vec![cond],
+        Stmt::Match { arg, with_arg, .. }
+        | Stmt::Switch { arg, with_arg, .. }
+        | Stmt::Fold { arg, with_arg, .. } => {
+            std::iter::once(arg.as_ref()).chain(with_arg).collect()
+        }
+        Stmt::Bend { arg, cond, .. } => arg.iter().chain(std::iter::once(cond.as_ref())).collect(),
+        Stmt::Return { term } => vec![term],
+        Stmt::With { .. } | Stmt::Open { .. } | Stmt::Err => vec![],
+    }
+}
+
+fn stmt_exprs_mut(stmt: &mut Stmt) -> Vec<&mut Expr> {
+    match stmt {
+        Stmt::Assign { val, .. } | Stmt::InPlace { val, .. } | Stmt::Ask { val, .. } => vec![val],
+        Stmt::Use { val, .. } => vec![val],
+        Stmt::If { cond, .. } => vec![cond],
+        Stmt::Match { arg, with_arg, .. }
+        | Stmt::Switch { arg, with_arg, .. }
+        | Stmt::Fold { arg, with_arg, .. } => {
+            std::iter::once(arg.as_mut()).chain(with_arg).collect()
+        }
+        Stmt::Bend { arg, cond, .. } => arg
+            .iter_mut()
+            .chain(std::iter::once(cond.as_mut()))
+            .collect(),
+        Stmt::Return { term } => vec![term],
+        Stmt::With { .. } | Stmt::Open { .. } | Stmt::Err => vec![],
+    }
+}
+
+pub(super) fn stmt_children(stmt: &Stmt) -> Vec<&Stmt> {
+    let (children, nxt): (Vec<&Stmt>, Option<&Stmt>) = match stmt {
+        Stmt::Assign { nxt, .. } => (vec![], nxt.as_deref()),
+        Stmt::InPlace { nxt, .. } | Stmt::Ask { nxt, .. } => (vec![], Some(nxt)),
+        Stmt::Open { nxt, .. } | Stmt::Use { nxt, .. } => (vec![], Some(nxt)),
+        Stmt::If {
+            then,
+            otherwise,
+            nxt,
+            ..
+        } => (vec![then, otherwise], nxt.as_deref()),
+        Stmt::Match { arms, nxt, .. } | Stmt::Fold { arms, nxt, .. } => {
+            (arms.iter().map(|arm| &arm.rgt).collect(), nxt.as_deref())
+        }
+        Stmt::Switch { arms, nxt, .. } => (arms.iter().collect(), nxt.as_deref()),
+        Stmt::Bend {
+            step, base, nxt, ..
+        } => (vec![step, base], nxt.as_deref()),
+        Stmt::With { bod, nxt, .. } => (vec![bod], nxt.as_deref()),
+        Stmt::Return { .. } | Stmt::Err => (vec![], None),
+    };
+
+    children.into_iter().chain(nxt).collect()
+}
+
+pub(super) fn stmt_children_mut(stmt: &mut Stmt) -> Vec<&mut Stmt> {
+    let (children, nxt): (Vec<&mut Stmt>, Option<&mut Stmt>) = match stmt {
+        Stmt::Assign { nxt, .. } => (vec![], nxt.as_deref_mut()),
+        Stmt::InPlace { nxt, .. } | Stmt::Ask { nxt, .. } => (vec![], Some(nxt)),
+        Stmt::Open { nxt, .. } | Stmt::Use { nxt, .. } => (vec![], Some(nxt)),
+        Stmt::If {
+            then,
+            otherwise,
+            nxt,
+            ..
+        } => (vec![then, otherwise], nxt.as_deref_mut()),
+        Stmt::Match { arms, nxt, .. } | Stmt::Fold { arms, nxt, .. } => (
+            arms.iter_mut().map(|arm| &mut arm.rgt).collect(),
+            nxt.as_deref_mut(),
+        ),
+        Stmt::Switch { arms, nxt, .. } => (arms.iter_mut().collect(), nxt.as_deref_mut()),
+        Stmt::Bend {
+            step, base, nxt, ..
+        } => (vec![step, base], nxt.as_deref_mut()),
+        Stmt::With { bod, nxt, .. } => (vec![bod], nxt.as_deref_mut()),
+        Stmt::Return { .. } | Stmt::Err => (vec![], None),
+    };
+
+    children.into_iter().chain(nxt).collect()
+}
@@ -0,0 +1,144 @@
+use rustpython_ast::Visitor;
+use rustpython_parser::ast::{
+    Expr, ExprAwait, ExprCall, ExprYield, ExprYieldFrom, Stmt, StmtAnnAssign, StmtAssign,
+    StmtAugAssign, StmtDelete, StmtFunctionDef, StmtGlobal, StmtImport, StmtImportFrom,
+    StmtNonlocal, StmtRaise, StmtTry, StmtWith,
+};
+
+// Builtins that talk to the outside world.
+const IMPURE_BUILTINS: [&str; 8] = [
+    "print",
+    "input",
+    "open",
+    "exec",
+    "eval",
+    "breakpoint",
+    "setattr",
+    "delattr",
+];
+
+// Methods of lists, dicts and sets that change the object they are called
+// on. Compiled classes may have methods of the same names, which are pure.
+const MUTATING_METHODS: [&str; 10] = [
+    "append", "extend", "insert", "pop", "remove", "clear", "update", "add", "sort", "reverse",
+];
+
+// Finds why an undecorated helper can't be run by Bend, if it can't. Only
+// `@bjit` functions are trusted without this check. `is_method` tells the
+// methods of the compiled classes.
+pub fn impurity(fun_def: &StmtFunctionDef, is_method: &dyn Fn(&str) -> bool) -> Option<String> {
+    let mut checker = Checker {
+        reason: None,
+        is_method,
+    };
+    for stmt in fun_def.body.clone() {
+        checker.visit_stmt(stmt);
+    }
+    checker.reason
+}
+
+struct Checker<'a> {
+    reason: Option<String>,
+    is_method: &'a dyn Fn(&str) -> bool,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, reason: String) {
+        self.reason.get_or_insert(reason);
+    }
+
+    fn check_target(&mut self, target: &Expr) {
+        match target {
+            Expr::Attribute(att) => self.report(format!("assigns to the attribute '{}'", att.attr)),
+            Expr::Subscript(_) => self.report("assigns to an item of a collection".to_string()),
+            Expr::Tuple(tup) => tup.elts.iter().for_each(|elt| self.check_target(elt)),
+            _ => {}
+        }
+    }
+}
+
+impl Visitor for Checker<'_> {
+    fn visit_stmt_global(&mut self, node: StmtGlobal) {
+        self.report(format!("uses the global variable '{}'", node.names[0]));
+    }
+
+    fn visit_stmt_nonlocal(&mut self, node: StmtNonlocal) {
+        self.report(format!("uses the nonlocal variable '{}'", node.names[0]));
+    }
+
+    fn visit_stmt_import(&mut self, _node: StmtImport) {
+        self.report("imports a module".to_string());
+    }
+
+    fn visit_stmt_import_from(&mut self, _node: StmtImportFrom) {
+        self.report("imports a module".to_string());
+    }
+
+    fn visit_stmt_raise(&mut self, _node: StmtRaise) {
+        self.report("raises an exception".to_string());
+    }
+
+    fn visit_stmt_try(&mut self, _node: StmtTry) {
+        self.report("handles exceptions".to_string());
+    }
+
+    fn visit_stmt_with(&mut self, _node: StmtWith) {
+        self.report("uses a context manager".to_string());
+    }
+
+    fn visit_stmt_delete(&mut self, _node: StmtDelete) {
+        self.report("deletes a variable".to_string());
+    }
+
+    fn visit_stmt_assign(&mut self, node: StmtAssign) {
+        node.targets
+            .iter()
+            .for_each(|target| self.check_target(target));
+        self.generic_visit_stmt_assign(node);
+    }
+
+    fn visit_stmt_aug_assign(&mut self, node: StmtAugAssign) {
+        self.check_target(&node.target);
+        self.generic_visit_stmt_aug_assign(node);
+    }
+
+    fn visit_stmt_ann_assign(&mut self, node: StmtAnnAssign) {
+        self.check_target(&node.target);
+        self.generic_visit_stmt_ann_assign(node);
+    }
+
+    fn visit_expr_call(&mut self, node: ExprCall) {
+        match node.func.as_ref() {
+            Expr::Name(name) if IMPURE_BUILTINS.contains(&name.id.as_str()) => {
+                self.report(format!("calls '{}'", name.id));
+            }
+            Expr::Attribute(att)
+                if MUTATING_METHODS.contains(&att.attr.as_str())
+                    && !(self.is_method)(&att.attr) =>
+            {
+                self.report(format!("calls the method '{}'", att.attr));
+            }
+            _ => {}
+        }
+        self.generic_visit_expr_call(node);
+    }
+
+    fn visit_expr_await(&mut self, _node: ExprAwait) {
+        self.report("awaits".to_string());
+    }
+
+    fn visit_expr_yield(&mut self, _node: ExprYield) {
+        self.report("is a generator".to_string());
+    }
+
+    fn visit_expr_yield_from(&mut self, _node: ExprYieldFrom) {
+        self.report("is a generator".to_string());
+    }
+
+    // Stops at the first reason found.
+    fn visit_stmt(&mut self, node: Stmt) {
+        if self.reason.is_none() {
+            self.generic_visit_stmt(node);
+        }
+    }
+}
@@ -0,0 +1,1314 @@
+use std::fs;
+
+use pyo3::{prelude::*, types::PyDict, wrap_pymodule};
+
+// Imports a Python module using benda, which checks its own results with
+// `assert`, along with the other modules of its package. The modules are
+// written to a directory of their own, and their names must be unique
+// among the tests, since they share `sys.modules`.
+//
+// Classes given to `benda.register_adt` are seen by every module, so their
+// fields must not be named like the ones of the other tests.
+fn check(modules: &[(&str, &str)]) {
+    pyo3::prepare_freethreaded_python();
+
+    Python::with_gil(|py| {
+        let (main, _) = modules[0];
+        let dir = std::env::temp_dir().join(format!("benda-tests-{}", main));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, code) in modules {
+            fs::write(dir.join(format!("{}.py", name)), code).unwrap();
+        }
+
+        let sys = py.import_bound("sys").unwrap();
+        let sys_modules = sys.getattr("modules").unwrap();
+        let sys_modules = sys_modules.downcast::<PyDict>().unwrap();
+        if !sys_modules.contains("benda").unwrap() {
+            let benda = wrap_pymodule!(crate::benda)(py);
+            sys_modules.set_item("benda", benda).unwrap();
+        }
+        let path = sys.getattr("path").unwrap();
+        path.call_method1("insert", (0, dir.to_str().unwrap()))
+            .unwrap();
+
+        if let Err(err) = py.import_bound(main) {
+            let traceback = err
+                .traceback_bound(py)
+                .and_then(|traceback| traceback.format().ok())
+                .unwrap_or_default();
+            panic!("{}{}", traceback, err);
+        }
+    });
+}
+
+#[test]
+fn dataclasses_are_read_back() {
+    check(&[(
+        "readback_dataclasses",
+        r#"
+from dataclasses import dataclass
+from benda import bjit
+
+@dataclass
+class Point:
+    x: int
+    y: int
+
+@dataclass
+class Seg:
+    start: Point
+    end: Point
+
+@bjit
+def make(a) -> Seg:
+    return Seg(Point(a, a + 1), Point(0, a))
+
+seg = make(3)
+assert seg == Seg(Point(3, 4), Point(0, 3))
+"#,
+    )]);
+}
+
+#[test]
+fn custom_types_use_the_bend_protocol() {
+    check(&[
+        (
+            "bend_protocol",
+            r#"
+from dataclasses import dataclass
+import benda
+from benda import bjit
+from bend_protocol_other import Pair as Single, grow
+
+class Vec2Protocol:
+    def __init__(self, dx, dy):
+        self.dx = dx
+        self.dy = dy
+    __match_args__ = ("dx", "dy")
+
+benda.register_adt(Vec2Protocol, from_bend=lambda dx, dy: (dx, dy))
+
+class Celsius:
+    def __init__(self, deg):
+        self.deg = deg
+    def __bend__(self):
+        return benda.Term(f"Vec2Protocol {{ dx: {self.deg}, dy: 1 }}")
+
+@bjit
+def swap(v) -> Vec2Protocol:
+    match v:
+        case Vec2Protocol(dx, dy):
+            return Vec2Protocol(dy, dx)
+
+swapped = swap(Vec2Protocol(1, 2))
+assert swapped == (2, 1)
+assert swap(Celsius(5)) == (1, 5)
+
+# Registered, but also defined in the module, where it is read from.
+@dataclass
+class Pair:
+    pa: int
+    pb: int
+
+benda.register_adt(Pair)
+
+@bjit
+def flip(p: Pair) -> Pair:
+    return Pair(p.pb, p.pa)
+
+@bjit
+def grow_single(s: Single) -> Single:
+    return grow(s)
+
+assert flip(Pair(1, 2)) == Pair(2, 1)
+assert grow_single(Single(1)) == Single(2)
+"#,
+        ),
+        (
+            "bend_protocol_other",
+            r#"
+from dataclasses import dataclass
+from benda import bjit
+
+# Named like a registered class of the main module, which must not hide it.
+@dataclass
+class Pair:
+    size: int
+
+@bjit
+def grow(p: Pair) -> Pair:
+    return Pair(p.size + 1)
+"#,
+        ),
+    ]);
+}
+
+#[test]
+fn tree_leaves_hold_any_value() {
+    check(&[(
+        "tree_leaves",
+        r#"
+from dataclasses import dataclass
+from benda import bjit, Tree, Node, Leaf, i24, f24
+
+@dataclass
+class Point:
+    x: int
+    y: int
+
+@bjit
+def first(tree) -> f24:
+    match tree:
+        case Node(left, right):
+            return first(left)
+        case Leaf(value):
+            return value
+
+@bjit
+def last(tree) -> Point:
+    match tree:
+        case Node(left, right):
+            return last(right)
+        case Leaf(value):
+            return value
+
+tree = Node(Leaf(f24(1.5)), Node(Leaf(i24(2)), Node(Leaf("a"), Leaf(Point(1, 2)))))
+value = first(tree)
+assert value == 1.5
+point = last(tree)
+assert point == Point(1, 2)
+"#,
+    )]);
+}
+
+#[test]
+fn trees_are_python_values() {
+    check(&[(
+        "tree_values",
+        r#"
+from benda import Tree, Node, Leaf
+
+tree = Tree.from_list([1, 2, 3, 4, 5])
+assert tree.to_list() == [1, 2, 3, 4, 5] and list(tree) == [1, 2, 3, 4, 5]
+assert tree.size() == 5 and tree.depth() == 3
+
+node = Node(Leaf(1), Node(Leaf(2), Leaf(3)))
+assert node.right.right.value == 3
+assert node == Node(Leaf(1), Node(Leaf(2), Leaf(3)))
+assert node != Node(Leaf(1), Leaf(2))
+assert Tree(Tree(node)) == Tree(node)
+
+def total(t):
+    match t:
+        case Node(left, right):
+            return total(left) + total(right)
+        case Leaf(value):
+            return value
+
+assert total(node) == 6
+"#,
+    )]);
+}
+
+#[test]
+fn deep_trees_are_converted_without_recursion() {
+    check(&[(
+        "deep_trees",
+        r#"
+from benda import bjit, Node, Leaf
+
+@bjit
+def count(tree):
+    match tree:
+        case Node(left, right):
+            return count(left) + count(right)
+        case Leaf(value):
+            return 1
+
+@bjit
+def ident(tree):
+    return tree
+
+tree = Leaf(0)
+for i in range(20000):
+    tree = Node(Leaf(i), tree)
+
+assert count(tree) == 20001
+same = ident(tree)
+assert same == tree
+assert repr(same).startswith("Node(Leaf(19999), Node(Leaf(19998), ")
+assert repr(same).endswith("Leaf(0))" + ")" * 19999)
+del same
+del tree
+"#,
+    )]);
+}
+
+#[test]
+fn number_operations_agree_with_hvm() {
+    check(&[(
+        "number_operations",
+        r#"
+from benda import bjit, u24, i24, f24
+
+@bjit
+def fmul(a: f24, b: f24) -> f24:
+    return a * b
+
+product = fmul(f24(1.5), f24(2.25))
+
+@bjit
+def umul(a: u24, b: u24) -> u24:
+    return a * b
+
+wrapped = umul(u24(5000), u24(5000))
+
+@bjit
+def ipow(a: i24, b: i24) -> i24:
+    return a ** b
+
+@bjit
+def ishl(a: i24, b: i24) -> i24:
+    return a << b
+
+@bjit
+def floor(a, b):
+    return a // b, a % b
+
+assert product == f24(1.5) * f24(2.25) == 3.375
+assert wrapped == u24(5000) * u24(5000) == 8222784
+assert ipow(i24(-7), i24(2)) == i24(-7) ** i24(2) == 49
+assert ishl(i24(-3), i24(4)) == i24(-3) << i24(4) == -48
+assert floor(-7, 2) == (-4, 1)
+assert floor(-7.5, 2.0) == (-4.0, 0.5)
+assert u24(3) - u24(5) == u24(16777214)
+assert i24(5) * 2 == 10 and i24(5) + 0.5 == 5.5
+"#,
+    )]);
+}
+
+#[test]
+fn i24_wraps_around_or_raises() {
+    check(&[(
+        "i24_wrap",
+        r#"
+from benda import bjit, i24
+
+@bjit
+def sub(a: i24, b: i24) -> i24:
+    return a - b
+
+difference = sub(i24(2), i24(7))
+
+assert i24(-1) == -1
+assert i24(2**23) == -2**23
+assert difference == -5
+assert i24(2**23 - 1) + 1 == -2**23
+
+try:
+    i24(2**23 - 1, checked=True) + 1
+    assert False
+except OverflowError:
+    pass
+"#,
+    )]);
+}
+
+#[test]
+fn f24_is_rounded_like_hvm() {
+    check(&[(
+        "f24_rounding",
+        r#"
+from benda import bjit, f24
+
+@bjit
+def ident(x: f24) -> f24:
+    return x
+
+same = ident(f24(0.1))
+
+@bjit
+def scale(x: f24) -> f24:
+    return x * 3.0 + 0.1
+
+scaled = scale(f24(1.1))
+
+assert same == f24(0.1)
+assert float(f24(0.1)) != 0.1
+assert scaled == f24(1.1) * 3.0 + 0.1
+"#,
+    )]);
+}
+
+#[test]
+fn literals_take_the_type_of_the_values() {
+    check(&[(
+        "typed_literals",
+        r#"
+from benda import bjit, f24, i24, u24
+
+@bjit
+def scale(x, n):
+    y = x * 2 + n
+    return y - 1
+
+scaled = scale(f24(1.5), 3)
+
+@bjit
+def absolute(x: i24):
+    if x < 0:
+        return -x
+    else:
+        return x
+
+positive = absolute(i24(-3))
+
+@bjit
+def half(n: u24) -> float:
+    return n / 2.0
+
+halved = half(u24(7))
+
+assert scaled == 5.0
+assert positive == 3
+assert halved == 3.5
+"#,
+    )]);
+}
+
+#[test]
+fn bignums_are_exact() {
+    check(&[(
+        "bignums",
+        r#"
+import benda
+from benda import bjit, f24, i24
+
+@bjit(bignum=True)
+def calc(x, y, op) -> int:
+    if op == 0:
+        return x + y
+    elif op == 1:
+        return x - y
+    elif op == 2:
+        return x * y
+    elif op == 3:
+        return x // y
+    else:
+        return x % y
+
+total = calc(1, 2, 0)
+
+@bjit(bignum=True)
+def less(x, y):
+    return x < y
+
+smaller = less(1, 2)
+
+@bjit(bignum=True)
+def fact(n) -> int:
+    if n < 2:
+        return 1
+    else:
+        return n * fact(n - 1)
+
+big = fact(30)
+
+@bjit(bignum=True)
+def square(x) -> int:
+    return x * x
+
+class Squared:
+    def __init__(self, n):
+        self.n = n
+    def __bend__(self):
+        return benda.Term(str(square(self.n)))
+
+# Converting the arguments calls a function with bignums, which must not
+# make the ones of the caller bignums too.
+@bjit
+def plus(a, b):
+    return a + b
+
+@bjit(bignum=True)
+def order(a, b) -> tuple[bool, bool]:
+    return a <= b, a >= b
+
+@bjit(bignum=True)
+def div(a, b) -> int:
+    return a // b
+
+@bjit(bignum=True)
+def mod(a, b) -> int:
+    return a % b
+
+@bjit(bignum=True)
+def power(a, b) -> int:
+    return a ** b
+
+@bjit
+def small_order(a, b) -> tuple[bool, bool]:
+    return a <= b, a >= b
+
+@bjit(bignum=True)
+def scaled(a, f: f24):
+    return a * f
+
+@bjit(bignum=True)
+def shifted(a, b: i24):
+    return a + b
+
+x = 123456789012345678901234567890
+y = -987654321098765
+assert total == 3
+assert [calc(x, y, op) for op in range(5)] == [x + y, x - y, x * y, x // y, x % y]
+assert smaller and less(x, y) == (x < y)
+assert big == 265252859812191058636308480000000
+assert plus(Squared(3), 5) == 14
+assert [order(x, x), order(y, x), order(x, y)] == [(True, True), (True, False), (False, True)]
+assert [small_order(1, 2), small_order(2, 2), small_order(-3, -4)] == [(True, False), (True, True), (False, True)]
+
+for f in (div, mod):
+    try:
+        f(x, 0)
+        assert False
+    except ZeroDivisionError:
+        pass
+
+try:
+    power(2, 3)
+    assert False
+except TypeError as e:
+    assert "**" in str(e)
+
+for f, arg in ((scaled, f24(1.5)), (shifted, i24(2))):
+    try:
+        f(3, arg)
+        assert False
+    except TypeError as e:
+        assert "converted" in str(e)
+"#,
+    )]);
+}
+
+#[test]
+fn ints_out_of_range_follow_the_overflow_policy() {
+    check(&[
+        (
+            "overflow_policy",
+            r#"
+import warnings
+import benda
+from benda import bjit
+from overflow_warn import add_big
+
+@bjit
+def inc(n):
+    return n + 1
+
+one = inc(0)
+
+@bjit(overflow="wrap")
+def inc_wrap(n):
+    return n + 1
+
+wrapped = inc_wrap(2**30 + 5)
+
+try:
+    inc(2**30)
+    assert False
+except OverflowError as e:
+    assert "n" in str(e) and str(2**30) in str(e)
+
+@bjit
+def add_huge(n):
+    return n + 30000000
+
+try:
+    add_huge(1)
+    assert False
+except OverflowError as e:
+    assert "30000000" in str(e)
+
+class Next:
+    def __init__(self, n):
+        self.n = n
+    def __bend__(self):
+        return benda.Term(str(inc_wrap(self.n)))
+
+# The policy of `inc_wrap`, called to convert the first argument, must not
+# be used for the second one.
+@bjit
+def add(a, b):
+    return a + b
+
+try:
+    add(Next(1), 2**30)
+    assert False
+except OverflowError as e:
+    assert "argument 'b'" in str(e)
+
+assert one == 1
+assert wrapped == 6
+
+with warnings.catch_warnings(record=True) as caught:
+    warnings.simplefilter("always")
+    add_big(1)
+    assert any(issubclass(w.category, RuntimeWarning) for w in caught)
+"#,
+        ),
+        (
+            "overflow_warn",
+            r#"
+from benda import bjit
+
+@bjit(overflow="warn")
+def add_big(n):
+    return n + 20000000
+
+big = add_big(1)
+"#,
+        ),
+    ]);
+}
+
+#[test]
+fn functions_are_values() {
+    check(&[(
+        "function_values",
+        r#"
+from benda import bjit, Tree, Node, Leaf
+
+@bjit
+def apply(f, x):
+    return f(x)
+
+@bjit
+def double(x):
+    return x * 2
+
+doubled = apply(double, 21)
+
+@bjit
+def use(x):
+    g = lambda y: y + 1
+    return apply(double, apply(g, x)) + apply(lambda z: z * 10, x)
+
+used = use(3)
+
+@bjit
+def map_tree(f, tree) -> Tree:
+    match tree:
+        case Node(left, right):
+            return Node(map_tree(f, left), map_tree(f, right))
+        case Leaf(value):
+            return Leaf(f(value))
+
+mapped = map_tree(double, Node(Leaf(1), Leaf(2)))
+
+assert doubled == 42
+assert used == 38
+assert mapped == Node(Leaf(2), Leaf(4))
+"#,
+    )]);
+}
+
+#[test]
+fn nested_functions_capture_their_variables() {
+    check(&[(
+        "nested_functions",
+        r#"
+from benda import bjit
+
+@bjit
+def outer(x, k):
+    def add_k(y):
+        return y + k
+    def fact(n):
+        if n == 0:
+            return 1
+        else:
+            return n * fact(n - 1)
+    return add_k(x) + fact(x)
+
+outer_value = outer(4, 10)
+
+@bjit
+def make_adder(n):
+    def add(y):
+        return y + n
+    return add
+
+@bjit
+def use(a, b):
+    f = make_adder(a)
+    return f(f(b))
+
+used = use(5, 7)
+
+@bjit
+def looping(n):
+    def count(i):
+        while i > 0:
+            i -= 1
+        return i
+    return count(n)
+
+assert outer_value == 38
+assert used == 17
+
+try:
+    looping(3)
+    assert False
+except SyntaxError as e:
+    assert "count" in str(e)
+"#,
+    )]);
+}
+
+#[test]
+fn tuples_are_built_and_destructured() {
+    check(&[(
+        "tuples",
+        r#"
+from benda import bjit
+
+@bjit
+def divmod_(a, b):
+    return a // b, a % b
+
+pair = divmod_(17, 5)
+
+@bjit
+def three(a, b):
+    return a, b, a + b
+
+triple = three(1, 2)
+
+@bjit
+def swap_sum(a, b):
+    a, b = b, a
+    q, r = divmod_(a, b)
+    match (q, r):
+        case (x, y):
+            s = x * 10 + y
+    return s, a - b
+
+swapped = swap_sum(5, 17)
+
+@bjit
+def classify(a, b):
+    match (a, b):
+        case (0, 0):
+            return 0
+        case (0, y):
+            return y
+        case (x, 1):
+            return x * 10
+        case _:
+            return a + b
+
+@bjit
+def named(a, b):
+    match (a, b):
+        case ("x", y):
+            return y
+        case _:
+            return 0
+
+assert pair == (3, 2)
+assert triple == (1, 2, 3)
+assert swapped == (32, 12)
+assert [classify(0, 0), classify(0, 5), classify(3, 1), classify(3, 4)] == [0, 5, 30, 7]
+
+try:
+    named(1, 2)
+    assert False
+except SyntaxError:
+    pass
+"#,
+    )]);
+}
+
+#[test]
+fn augmented_and_chained_assignments() {
+    check(&[(
+        "assignments",
+        r#"
+from dataclasses import dataclass
+from benda import bjit
+
+@dataclass
+class Counter:
+    hits: int
+
+@bjit
+def f(n):
+    total: int = 0
+    a = b = n * 2
+    total += a
+    total *= 3
+    total -= b // 2
+    x: float = 1
+    x += 0.5
+    return total, x
+
+result = f(4)
+
+assert result == (20, 1.5)
+
+# Bend values can't be changed in place.
+@bjit
+def bump(c: Counter) -> int:
+    c.hits += 1
+    return c.hits
+
+@bjit
+def reset(c: Counter) -> int:
+    c.hits: int = 0
+    return c.hits
+
+@bjit
+def store(a) -> int:
+    a[0] = 1
+    return a
+
+for f, arg in ((bump, Counter(1)), (reset, Counter(1)), (store, 1)):
+    try:
+        f(arg)
+        assert False
+    except TypeError:
+        pass
+
+@bjit
+def starred(a):
+    x, *y = a, a
+    return x
+
+try:
+    starred(1)
+    assert False
+except SyntaxError:
+    pass
+"#,
+    )]);
+}
+
+#[test]
+fn fields_are_read_from_dataclasses() {
+    check(&[(
+        "field_access",
+        r#"
+from dataclasses import dataclass
+from benda import bjit
+
+@dataclass
+class Point:
+    x: int
+    y: int
+
+@dataclass
+class Seg:
+    start: Point
+    end: Point
+
+@dataclass
+class Circle:
+    r: float
+
+@dataclass
+class Square:
+    side: float
+
+Shape = Circle | Square
+
+@bjit
+def length2(s: Seg):
+    dx = s.end.x - s.start.x
+    dy = s.end.y - s.start.y
+    return dx * dx + dy * dy
+
+@bjit
+def radius(s: Shape):
+    return s.r
+
+squared = length2(Seg(Point(1, 2), Point(4, 6)))
+
+assert squared == 25
+
+try:
+    radius(Circle(1.0))
+    assert False
+except TypeError as e:
+    assert "more than one constructor" in str(e)
+"#,
+    )]);
+}
+
+#[test]
+fn keyword_arguments_and_defaults() {
+    check(&[(
+        "keyword_arguments",
+        r#"
+from dataclasses import dataclass
+from benda import bjit
+
+@dataclass
+class Point:
+    x: int
+    y: int
+
+@bjit
+def scale(p, k=2, offset=0) -> Point:
+    match p:
+        case Point(x, y):
+            return Point(x=x * k + offset, y=y * k)
+
+@bjit
+def run(a, b, *, k=3) -> tuple[Point, Point]:
+    return scale(Point(y=b, x=a), offset=1), scale(Point(a, b), k, offset=k)
+
+assert run(1, 2) == (Point(3, 4), Point(6, 6))
+assert run(b=2, a=1, k=10) == (Point(3, 4), Point(20, 20))
+assert scale(Point(1, 1), offset=5) == Point(7, 2)
+
+def add(a, b=2):
+    return a + b
+
+@bjit
+def unexpected(x):
+    return add(x, c=1)
+
+@bjit
+def twice(x):
+    return add(x, a=1)
+
+@bjit
+def missing(x):
+    return add(b=x)
+
+@bjit
+def many(x):
+    return add(x, x, x)
+
+for f in (unexpected, twice, missing, many):
+    try:
+        f(1)
+        assert False
+    except TypeError as e:
+        assert "add" in str(e)
+"#,
+    )]);
+}
+
+#[test]
+fn pure_helpers_are_compiled() {
+    check(&[(
+        "helpers",
+        r#"
+import benda
+from benda import bjit
+
+def square(x):
+    return x * x
+
+def sum_squares(n):
+    if n == 0:
+        return 0
+    else:
+        return square(n) + sum_squares(n - 1)
+
+def noisy(x):
+    print(x)
+    return x
+
+@benda.bjit
+def run(n):
+    return sum_squares(n)
+
+@bjit(overflow="wrap")
+def broken(n):
+    return noisy(n)
+
+assert run(10) == 385
+
+try:
+    broken(3)
+    assert False
+except TypeError as e:
+    assert "not pure" in str(e)
+"#,
+    )]);
+}
+
+#[test]
+fn globals_are_given_to_bend() {
+    check(&[(
+        "globals",
+        r#"
+from dataclasses import dataclass
+from benda import bjit, Tree, Node, Leaf
+
+DEPTH = 10
+WEIGHTS = (1, 2, 3)
+NAME = "benda"
+TREE = Node(Leaf(1), Leaf(2))
+
+@dataclass
+class Point:
+    x: int
+    y: int
+
+ORIGIN = Point(3, 4)
+LOOKUP = {1: 2}
+
+def weighted(a, b, c):
+    w1, w2, w3 = WEIGHTS
+    return a * w1 + b * w2 + c * w3
+
+@bjit
+def run(n):
+    return weighted(n, n, n) + DEPTH, NAME, ORIGIN.x + ORIGIN.y
+
+@bjit
+def tree() -> Tree:
+    return TREE
+
+@bjit
+def bad():
+    return LOOKUP
+
+assert run(1) == (16, "benda", 7)
+assert tree() == TREE
+
+try:
+    bad()
+    assert False
+except TypeError as e:
+    assert "LOOKUP" in str(e)
+"#,
+    )]);
+}
+
+#[test]
+fn functions_and_classes_of_other_modules() {
+    check(&[
+        (
+            "modules_main",
+            r#"
+import functools
+from dataclasses import dataclass
+from benda import bjit
+import modules_shapes
+from modules_shapes import Circle, area as circle_area, make
+
+@dataclass
+class Point:
+    x: int
+    y: int
+
+@bjit
+def f(n, c: Circle, p: Point):
+    return circle_area(c) + modules_shapes.area(make(n)) + p.y
+
+@bjit
+def g(n) -> Circle:
+    return make(n)
+
+assert f(2, Circle(5), Point(1, 7)) == 30 + 12 + 7
+assert g(4) == Circle(4)
+
+try:
+    bjit(functools.partial(f, 2))(Circle(5), Point(1, 7))
+    assert False
+except TypeError as e:
+    assert "partial" in str(e)
+
+def outer():
+    @bjit
+    def inner(x):
+        return x + 1
+    return inner(1)
+
+try:
+    outer()
+    assert False
+except TypeError as e:
+    assert "inner" in str(e)
+"#,
+        ),
+        (
+            "modules_shapes",
+            r#"
+from dataclasses import dataclass
+from benda import bjit
+
+SCALE = 3
+
+@dataclass
+class Circle:
+    radius: int
+
+# Named like a class of the main module, which must not clash with it.
+@dataclass
+class Point:
+    a: int
+    b: int
+
+def double(x):
+    return x * 2
+
+@bjit
+def area(c: Circle):
+    return double(c.radius) * SCALE
+
+def make(r):
+    return Circle(radius=r)
+"#,
+        ),
+    ]);
+}
+
+#[test]
+fn python_names_are_escaped() {
+    check(&[(
+        "escaped_names",
+        r#"
+from dataclasses import dataclass
+from benda import bjit
+
+@dataclass
+class List:
+    open: int
+    fold: int
+
+@dataclass
+class Café:
+    größe: int
+
+def main(bend, fold):
+    return bend * 10 + fold
+
+@bjit
+def f(switch, lst: List, c: Café) -> List:
+    match = main(switch, lst.fold)
+    return List(match + c.größe, fold=lst.open)
+
+assert f(3, List(5, 7), Café(100)) == List(137, 5)
+"#,
+    )]);
+}
+
+#[test]
+fn dataclass_annotations_are_understood() {
+    check(&[(
+        "dataclass_annotations",
+        r#"
+from dataclasses import dataclass
+from typing import ClassVar, Optional
+from benda import bjit
+
+@dataclass
+class Cell:
+    """A linked list of ints."""
+    value: int
+    next: Optional["Cell"]
+    count: ClassVar[int] = 0
+
+    def describe(self):
+        return "cell"
+
+@dataclass
+class Empty:
+    pass
+
+@bjit
+def first(c: Cell) -> int:
+    return c.value
+
+@bjit
+def length(c: Optional[Cell]) -> int:
+    if c is None:
+        return 0
+    else:
+        return 1 + length(c.next)
+
+@bjit
+def build(n) -> Optional[Cell]:
+    if n == 0:
+        return None
+    else:
+        return Cell(n, build(n - 1))
+
+@bjit
+def same(e: Empty) -> Empty:
+    return e
+
+assert first(Cell(1, None)) == 1
+assert length(Cell(1, Cell(2, None))) == 2
+assert length(None) == 0
+assert build(2) == Cell(2, Cell(1, None))
+assert same(Empty()) == Empty()
+
+@bjit
+def identical(a, b):
+    return a is b
+
+@bjit
+def missing(c):
+    return c is None
+
+for f, args in ((identical, (1, 2)), (missing, (None,))):
+    try:
+        f(*args)
+        assert False
+    except TypeError:
+        pass
+"#,
+    )]);
+}
+
+#[test]
+fn unions_are_datatypes() {
+    check(&[(
+        "unions",
+        r#"
+from dataclasses import dataclass
+from typing import TypeAlias, Union
+from benda import bjit
+
+@dataclass
+class Circle:
+    r: int
+
+@dataclass
+class Square:
+    side: int
+
+@dataclass
+class Ball:
+    d: int
+
+Shape = Circle | Square
+Round: TypeAlias = Union[Circle, Ball]
+
+A = 1
+B = 4
+FLAGS = A | B
+
+def area(s: Shape) -> int:
+    match s:
+        case Circle(r):
+            return 3 * r * r
+        case Square(side):
+            return side * side
+
+def size(x: Round) -> int:
+    match x:
+        case Circle(r):
+            return 2 * r
+        case Ball(d):
+            return d
+
+@bjit
+def total(n) -> int:
+    return area(Circle(n)) + area(Square(n)) + size(Circle(n)) + size(Ball(n)) + FLAGS
+
+@bjit
+def largest(n) -> Shape:
+    return Circle(n)
+
+assert total(2) == 12 + 4 + 4 + 2 + 5
+assert largest(3) == Circle(3)
+"#,
+    )]);
+}
+
+#[test]
+fn enums_are_nullary_constructors() {
+    check(&[(
+        "enums",
+        r#"
+from enum import Enum, auto
+from benda import bjit
+
+class Light(Enum):
+    RED = auto()
+    YELLOW = auto()
+    GREEN = auto()
+
+def step(l: Light) -> Light:
+    match l:
+        case Light.RED:
+            return Light.GREEN
+        case Light.GREEN:
+            return Light.YELLOW
+        case Light.YELLOW:
+            return Light.RED
+
+@bjit
+def run(l: Light, n) -> Light:
+    if n == 0:
+        return l
+    else:
+        return run(step(l), n - 1)
+
+assert run(Light.RED, 1) is Light.GREEN
+assert run(Light.RED, 2) is Light.YELLOW
+assert run(Light.RED, 3) is Light.RED
+"#,
+    )]);
+}
+
+#[test]
+fn methods_are_bend_functions() {
+    check(&[(
+        "methods",
+        r#"
+from dataclasses import dataclass
+from benda import bjit
+
+@dataclass
+class Point:
+    x: int
+    y: int
+
+    def add(self, other: "Point") -> "Point":
+        return Point(self.x + other.x, self.y + other.y)
+
+    def norm1(self) -> int:
+        return self.x + self.y
+
+    @classmethod
+    def origin(cls) -> "Point":
+        return cls(0, 0)
+
+    @bjit
+    def scaled(self, k) -> "Point":
+        return Point(self.x * k, self.y * k)
+
+@bjit
+def f(p: Point, q: Point):
+    o = Point.origin().add(p)
+    return p.add(q).norm1() + o.x
+
+p = Point(1, 2)
+assert p.scaled(3) == Point(3, 6)
+assert Point.scaled(p, 2) == Point(2, 4)
+assert f(p, Point(3, 4)) == 11
+
+def untyped(p):
+    return p.norm1()
+
+@bjit
+def g(p: Point):
+    return untyped(p)
+
+try:
+    g(p)
+    assert False
+except TypeError as e:
+    assert "norm1" in str(e)
+"#,
+    )]);
+}
@@ -0,0 +1,186 @@
+use std::cell::Cell;
+
+use bend::{
+    fun::{Num, Pattern, Term},
+    imp,
+};
+use num_bigint::{BigInt, BigUint, Sign};
+use num_traits::{ToPrimitive, Zero};
+use pyo3::{exceptions::PyZeroDivisionError, prelude::*, sync::GILProtected, types::PyLong};
+
+use super::ToBendResult;
+
+// Bignums are lists with their sign (0 or 1) followed by their limbs, from
+// the least significant one. Each limb has 12 bits, so the product of two
+// limbs plus a carry still fits in an u24.
+pub const LIMB_BITS: u32 = 12;
+pub const LIMB_MASK: u32 = (1 << LIMB_BITS) - 1;
+
+// Set while a function decorated with `@bjit(bignum=True)` is called, so
+// every Python int it receives becomes a bignum, including the ones inside
+// dataclasses and trees.
+static ENABLED: GILProtected<Cell<bool>> = GILProtected::new(Cell::new(false));
+
+pub fn enabled(py: Python<'_>) -> bool {
+    ENABLED.get(py).get()
+}
+
+// Returns whether they were enabled before, to put it back.
+pub fn set_enabled(py: Python<'_>, enabled: bool) -> bool {
+    ENABLED.get(py).replace(enabled)
+}
+
+pub fn to_bend(value: &Bound<'_, PyLong>) -> ToBendResult {
+    Ok(literal(&value.extract::<BigInt>()?))
+}
+
+pub fn literal(value: &BigInt) -> imp::Expr {
+    let sign = u32::from(value.sign() == Sign::Minus);
+    let mut magnitude = value.magnitude().clone();
+
+    let mut els = vec![sign];
+    while !magnitude.is_zero() {
+        els.push((&magnitude & BigUint::from(LIMB_MASK)).to_u32().unwrap());
+        magnitude >>= LIMB_BITS;
+    }
+
+    imp::Expr::Lst {
+        els: els
+            .into_iter()
+            .map(|val| imp::Expr::Num { val: Num::U24(val) })
+            .collect(),
+    }
+}
+
+// Reads a bignum returned by HVM, or None if the term is not one. The list
+// may come back as a Bend list or still encoded, as `λx (x 1 head tail)`.
+// The sign 2 is given by a division by zero.
+pub fn from_bend(term: &Term) -> Option<PyResult<BigInt>> {
+    let mut nums: Vec<u32> = vec![];
+    let mut term = term;
+
+    loop {
+        term = match term {
+            Term::List { els } => {
+                for el in els {
+                    nums.push(num(el)?);
+                }
+                break;
+            }
+            Term::Ref { nam } if nam.as_ref() == "List/Nil" => break,
+            _ => match encoded_ctr(term)?.as_slice() {
+                [tag] if num(tag)? == 0 => break,
+                [tag, head, tail] if num(tag)? == 1 => {
+                    nums.push(num(head)?);
+                    tail
+                }
+                _ => return None,
+            },
+        };
+    }
+
+    let (sign, limbs) = nums.split_first()?;
+    if *sign == 2 {
+        return Some(Err(PyZeroDivisionError::new_err(
+            "integer division or modulo by zero",
+        )));
+    }
+
+    let mut value = BigInt::zero();
+    for limb in limbs.iter().rev() {
+        value = (value << LIMB_BITS) + limb;
+    }
+
+    Some(Ok(if *sign == 1 { -value } else { value }))
+}
+
+fn num(term: &Term) -> Option<u32> {
+    match term {
+        Term::Num { val: Num::U24(val) } => Some(*val),
+        _ => None,
+    }
+}
+
+// The tag and fields of a constructor encoded as `λx (x tag f1 .. fn)`.
+fn encoded_ctr(term: &Term) -> Option<Vec<&Term>> {
+    let (bind, bod) = match term {
+        Term::Lam { pat, bod, .. } => match pat.as_ref() {
+            Pattern::Var(Some(bind)) => (bind, bod.as_ref()),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    let mut args: Vec<&Term> = vec![];
+    let mut head = bod;
+    while let Term::App { fun, arg, .. } = head {
+        args.push(arg.as_ref());
+        head = fun.as_ref();
+    }
+
+    match head {
+        Term::Var { nam } if nam == bind => {
+            args.reverse();
+            Some(args)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bend::{
+        fun::{Num, Term},
+        imp,
+    };
+    use num_bigint::BigInt;
+
+    use super::{from_bend, literal};
+
+    fn limbs(value: &BigInt) -> Vec<u32> {
+        let imp::Expr::Lst { els } = literal(value) else {
+            panic!("A bignum is a list");
+        };
+        els.into_iter()
+            .map(|el| match el {
+                imp::Expr::Num { val: Num::U24(val) } => val,
+                _ => panic!("The limbs of a bignum are u24s"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn literals_are_sign_and_12_bit_limbs() {
+        assert_eq!(limbs(&BigInt::from(0)), vec![0]);
+        assert_eq!(limbs(&BigInt::from(4095)), vec![0, 4095]);
+        assert_eq!(limbs(&BigInt::from(4096)), vec![0, 0, 1]);
+        assert_eq!(limbs(&BigInt::from(-5)), vec![1, 5]);
+    }
+
+    #[test]
+    fn limbs_are_read_back_into_the_same_int() {
+        let values = [
+            BigInt::from(0),
+            BigInt::from(1),
+            BigInt::from(-1),
+            BigInt::from(1u64 << 40),
+            BigInt::from(3).pow(80),
+            -BigInt::from(7).pow(45),
+        ];
+
+        for value in values {
+            let els = limbs(&value)
+                .into_iter()
+                .map(|val| Term::Num { val: Num::U24(val) })
+                .collect();
+            let read = from_bend(&Term::List { els }).map(Result::unwrap);
+            assert_eq!(read, Some(value));
+        }
+    }
+
+    #[test]
+    fn division_by_zero_is_read_back_as_an_error() {
+        let els = vec![Term::Num { val: Num::U24(2) }];
+        assert!(matches!(from_bend(&Term::List { els }), Some(Err(_))));
+    }
+}
@@ -0,0 +1,36 @@
+use bend::{fun::Name, imp};
+use pyo3::{prelude::*, types::PyType};
+
+use super::{modules::bend_name, names::escape, ToBendResult};
+
+fn enum_class(py: Python<'_>) -> PyResult<Bound<'_, PyAny>> {
+    py.import_bound("enum")?.getattr("Enum")
+}
+
+pub fn is_enum(class: &Bound<'_, PyAny>) -> bool {
+    let Ok(class) = class.downcast::<PyType>() else {
+        return false;
+    };
+    enum_class(class.py()).is_ok_and(|enum_class| class.is_subclass(&enum_class).unwrap_or(false))
+}
+
+// Converts a member of an `enum.Enum` into the constructor named after it,
+// like `Color/RED` for `Color.RED`.
+pub fn extract_enum(arg: &Bound<'_, PyAny>) -> Option<ToBendResult> {
+    let enum_class = enum_class(arg.py()).ok()?;
+    if !arg.is_instance(&enum_class).unwrap_or(false) {
+        return None;
+    }
+
+    let name = (|| -> PyResult<String> {
+        let class = bend_name(&arg.get_type())?;
+        let member = arg.getattr("name")?.to_string();
+        Ok(format!("{}/{}", class, escape(&member)))
+    })();
+
+    Some(name.map(|name| imp::Expr::Ctr {
+        name: Name::new(name),
+        args: vec![],
+        kwargs: vec![],
+    }))
+}
@@ -1,9 +1,13 @@
-use std::ops::{Add, Sub};
-
 use bend::imp;
-use pyo3::{pyclass, pymethods};
+use pyo3::{
+    exceptions::PyValueError,
+    prelude::*,
+    pyclass::CompareOp,
+    pymethods,
+    types::{PyFloat, PyString},
+};
 
-use super::{BendType, ToBendResult};
+use super::{py_operator, BendType, ToBendResult};
 
 #[pyclass(module = "benda")]
 #[allow(non_camel_case_types)]
@@ -19,9 +23,18 @@ impl BendType for f24 {
 }
 
 impl f24 {
-    // TODO: Implement a masking for float numbers.
+    // Rounds the value to the 24-bit float used by HVM, which is an f32 with
+    // the 8 lower bits of the mantissa dropped. Like HVM, ties round to even
+    // and NaNs are kept from becoming infinities.
     pub fn new(value: f32) -> Self {
-        Self(value)
+        let bits = value.to_bits();
+        let mut shifted = bits >> 8;
+        let lost = bits & 0xff;
+
+        shifted += u32::from(!value.is_nan()) & ((lost - ((lost >> 7) & !shifted)) >> 7);
+        shifted |= u32::from(value.is_nan());
+
+        Self(f32::from_bits(shifted.wrapping_shl(8)))
     }
 
     pub fn get(self) -> f32 {
@@ -53,6 +66,9 @@ impl From<f24> for f32 {
     }
 }
 
+// The operations follow HVM, which computes them on 32-bit floats and
+// rounds the result back to 24 bits.
+
 impl std::ops::Add for f24 {
     type Output = Self;
 
@@ -69,8 +85,79 @@ impl std::ops::Sub for f24 {
     }
 }
 
-// TODO: Check for overflow on the operations
-// TODO: Implement tests for each operation comparing to Bend
+impl std::ops::Mul for f24 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        f24::new(self.0 * other.0)
+    }
+}
+
+impl std::ops::Div for f24 {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        f24::new(self.0 / other.0)
+    }
+}
+
+// Like HVM, the remainder has the sign of the dividend.
+impl std::ops::Rem for f24 {
+    type Output = Self;
+
+    fn rem(self, other: Self) -> Self {
+        f24::new(self.0 % other.0)
+    }
+}
+
+impl f24 {
+    pub fn pow(self, other: Self) -> Self {
+        f24::new(self.0.powf(other.0))
+    }
+
+    // HVM has no floor division, this is the floor of the division.
+    pub fn floor_div(self, other: Self) -> Self {
+        f24::new((self / other).0.floor())
+    }
+
+    // Unlike the remainder, the modulo has the sign of the divisor.
+    pub fn modulo(self, other: Self) -> Self {
+        let r = self % other;
+        if r.0 != 0.0 && (r.0 < 0.0) != (other.0 < 0.0) {
+            r + other
+        } else {
+            r
+        }
+    }
+
+    // Any Python number can be an operand, including u24 and i24.
+    fn operand(other: &Bound<'_, PyAny>) -> Option<Self> {
+        if let Ok(other) = other.extract::<Self>() {
+            return Some(other);
+        }
+
+        if other.is_instance_of::<PyString>() {
+            return None;
+        }
+
+        other.extract::<f32>().ok().map(f24::new)
+    }
+
+    fn binary(
+        &self,
+        other: &Bound<'_, PyAny>,
+        reflected: bool,
+        op: impl Fn(Self, Self) -> Self,
+    ) -> PyObject {
+        let py = other.py();
+
+        match Self::operand(other) {
+            Some(other) if reflected => op(other, *self).into_py(py),
+            Some(other) => op(*self, other).into_py(py),
+            None => py.NotImplemented(),
+        }
+    }
+}
 
 #[pymethods]
 impl f24 {
@@ -79,15 +166,188 @@ impl f24 {
         f24::new(value)
     }
 
-    fn __add__(&self, other: &Self) -> Self {
-        f24::add(*self, *other)
+    fn __add__(&self, other: &Bound<'_, PyAny>) -> PyObject {
+        self.binary(other, false, |a, b| a + b)
+    }
+
+    fn __radd__(&self, other: &Bound<'_, PyAny>) -> PyObject {
+        self.binary(other, true, |a, b| a + b)
+    }
+
+    fn __sub__(&self, other: &Bound<'_, PyAny>) -> PyObject {
+        self.binary(other, false, |a, b| a - b)
+    }
+
+    fn __rsub__(&self, other: &Bound<'_, PyAny>) -> PyObject {
+        self.binary(other, true, |a, b| a - b)
+    }
+
+    fn __mul__(&self, other: &Bound<'_, PyAny>) -> PyObject {
+        self.binary(other, false, |a, b| a * b)
+    }
+
+    fn __rmul__(&self, other: &Bound<'_, PyAny>) -> PyObject {
+        self.binary(other, true, |a, b| a * b)
+    }
+
+    // Division by zero gives infinity or NaN, as in HVM.
+    fn __truediv__(&self, other: &Bound<'_, PyAny>) -> PyObject {
+        self.binary(other, false, |a, b| a / b)
+    }
+
+    fn __rtruediv__(&self, other: &Bound<'_, PyAny>) -> PyObject {
+        self.binary(other, true, |a, b| a / b)
+    }
+
+    fn __floordiv__(&self, other: &Bound<'_, PyAny>) -> PyObject {
+        self.binary(other, false, f24::floor_div)
+    }
+
+    fn __rfloordiv__(&self, other: &Bound<'_, PyAny>) -> PyObject {
+        self.binary(other, true, f24::floor_div)
+    }
+
+    fn __mod__(&self, other: &Bound<'_, PyAny>) -> PyObject {
+        self.binary(other, false, f24::modulo)
+    }
+
+    fn __rmod__(&self, other: &Bound<'_, PyAny>) -> PyObject {
+        self.binary(other, true, f24::modulo)
+    }
+
+    fn __pow__(
+        &self,
+        other: &Bound<'_, PyAny>,
+        modulo: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        if modulo.is_some() {
+            return Err(PyValueError::new_err("f24 pow() does not support a modulo"));
+        }
+        Ok(self.binary(other, false, f24::pow))
+    }
+
+    fn __rpow__(
+        &self,
+        other: &Bound<'_, PyAny>,
+        modulo: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        if modulo.is_some() {
+            return Err(PyValueError::new_err("f24 pow() does not support a modulo"));
+        }
+        Ok(self.binary(other, true, f24::pow))
+    }
+
+    fn __neg__(&self) -> Self {
+        f24::new(-self.0)
+    }
+
+    fn __pos__(&self) -> Self {
+        *self
+    }
+
+    fn __abs__(&self) -> Self {
+        f24::new(self.0.abs())
+    }
+
+    // Compares by value with f24s and with any Python number.
+    fn __richcmp__(&self, other: &Bound<'_, PyAny>, op: CompareOp) -> PyResult<PyObject> {
+        let py = other.py();
+        let other = match other.extract::<Self>() {
+            Ok(other) => other.0.into_py(py),
+            Err(_) => other.clone().unbind(),
+        };
+
+        let name = match op {
+            CompareOp::Lt => "lt",
+            CompareOp::Le => "le",
+            CompareOp::Eq => "eq",
+            CompareOp::Ne => "ne",
+            CompareOp::Gt => "gt",
+            CompareOp::Ge => "ge",
+        };
+
+        py_operator(py, name, self.0, other)
+    }
+
+    // Equal to the hash of the float with the same value, since they compare equal.
+    fn __hash__(&self, py: Python<'_>) -> PyResult<isize> {
+        self.0.into_py(py).bind(py).hash()
+    }
+
+    fn __bool__(&self) -> bool {
+        self.0 != 0.0
+    }
+
+    // Truncates like `int(float)`, raising for infinities and NaN.
+    fn __int__(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let val = PyFloat::new_bound(py, self.0 as f64);
+        Ok(val.call_method0("__int__")?.unbind())
+    }
+
+    fn __float__(&self) -> f64 {
+        self.0 as f64
     }
 
-    fn __sub__(&self, other: &Self) -> Self {
-        f24::sub(*self, *other)
+    fn __repr__(&self) -> String {
+        format!("f24({})", self.0)
     }
 
     fn __str__(&self) -> String {
         self.0.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use hvm::hvm::Numb;
+
+    use super::f24;
+
+    fn assert_like_hvm(value: f32) {
+        let rounded = f24::new(value).get();
+        let expected = Numb::new_f24(value).get_f24();
+
+        match expected.is_nan() {
+            true => assert!(rounded.is_nan(), "{:e} rounded to {:e}", value, rounded),
+            false => assert_eq!(rounded.to_bits(), expected.to_bits(), "{:e}", value),
+        }
+    }
+
+    #[test]
+    fn rounds_like_hvm() {
+        let values = [
+            0.0,
+            -0.0,
+            1.0,
+            0.1,
+            -2.5,
+            std::f32::consts::PI,
+            1e-40,
+            1e38,
+            f32::MAX,
+            f32::MIN_POSITIVE,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NAN,
+        ];
+        for value in values {
+            assert_like_hvm(value);
+        }
+
+        for bits in (0..=u32::MAX).step_by(65_537) {
+            assert_like_hvm(f32::from_bits(bits));
+        }
+    }
+
+    #[test]
+    fn ties_round_to_even() {
+        // 1.0 with half of the lowest kept bit, and then 1.0 plus that bit.
+        assert_eq!(f24::new(f32::from_bits(0x3f80_0080)).get(), 1.0);
+        assert_eq!(
+            f24::new(f32::from_bits(0x3f80_0180)).get().to_bits(),
+            0x3f80_0200
+        );
+        assert_like_hvm(f32::from_bits(0x3f80_0080));
+        assert_like_hvm(f32::from_bits(0x3f80_0180));
+    }
+}
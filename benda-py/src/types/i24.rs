@@ -1,14 +1,20 @@
-use std::ops::{Add, Sub};
-
 use bend::imp;
-use pyo3::{pyclass, pymethods};
+use pyo3::{
+    exceptions::{PyOverflowError, PyValueError},
+    prelude::*,
+    pyclass::CompareOp,
+    pymethods,
+    types::{PyFloat, PyLong},
+};
 
-use super::{BendType, ToBendResult};
+use super::{py_operator, BendType, ToBendResult};
 
+// A 24-bit two's complement integer. Checked values raise an
+// `OverflowError` instead of wrapping around when a result doesn't fit.
 #[pyclass(module = "benda")]
 #[allow(non_camel_case_types)]
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub struct i24(i32);
+#[derive(Clone, Copy)]
+pub struct i24(i32, bool);
 
 impl BendType for i24 {
     fn to_bend(&self) -> ToBendResult {
@@ -19,16 +25,58 @@ impl BendType for i24 {
 }
 
 impl i24 {
-    const MAX: i32 = 0xffffff;
+    pub const MIN: i64 = -(1 << 23);
+    pub const MAX: i64 = (1 << 23) - 1;
+    const MASK: i64 = 0xffffff;
 
-    // TODO: Check if the masking is working properly
+    // Keeps the lower 24 bits and sign extends them, like HVM does.
     pub fn new(value: i32) -> Self {
-        Self(value & Self::MAX)
+        Self(value << 8 >> 8, false)
     }
 
     pub fn get(self) -> i32 {
         self.0
     }
+
+    pub fn is_checked(self) -> bool {
+        self.1
+    }
+
+    fn fits(value: i64) -> bool {
+        (Self::MIN..=Self::MAX).contains(&value)
+    }
+
+    fn overflow(value: impl std::fmt::Display) -> PyErr {
+        PyOverflowError::new_err(format!(
+            "i24 overflow, {} is out of the range [{}, {}]",
+            value,
+            Self::MIN,
+            Self::MAX
+        ))
+    }
+
+    // Builds the result of an operation, which can't wrap around if checked.
+    fn from_result(value: i64, checked: bool) -> PyResult<Self> {
+        if checked && !Self::fits(value) {
+            return Err(Self::overflow(value));
+        }
+
+        Ok(Self(i24::new(value as i32).0, checked))
+    }
+
+    // Python ints are wrapped into 24 bits, unless the operation is checked.
+    pub fn from_int(value: &Bound<'_, PyLong>, checked: bool) -> PyResult<Self> {
+        if let Ok(value) = value.extract::<i64>() {
+            return Self::from_result(value, checked);
+        }
+
+        if checked {
+            return Err(Self::overflow(value));
+        }
+
+        let masked: i64 = value.bitand(Self::MASK)?.extract()?;
+        Self::from_result(masked, false)
+    }
 }
 
 impl std::fmt::Debug for i24 {
@@ -55,11 +103,31 @@ impl From<i24> for i32 {
     }
 }
 
+impl PartialEq for i24 {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for i24 {}
+
+impl PartialOrd for i24 {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for i24 {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
 impl std::ops::Add for i24 {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
-        i24::new(self.0 + other.0)
+        i24::new(self.0.wrapping_add(other.0))
     }
 }
 
@@ -67,29 +135,373 @@ impl std::ops::Sub for i24 {
     type Output = Self;
 
     fn sub(self, other: Self) -> Self {
-        i24::new(self.0 - other.0)
+        i24::new(self.0.wrapping_sub(other.0))
+    }
+}
+
+impl std::ops::Mul for i24 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        i24::new(self.0.wrapping_mul(other.0))
+    }
+}
+
+// The operations compute the exact result of the 24-bit operands, which is
+// then wrapped like HVM does, keeping the lower 24 bits. Division truncates
+// towards zero, like in HVM, while floor division and modulo round down,
+// like in Python.
+mod op {
+    use pyo3::{exceptions::PyZeroDivisionError, PyResult};
+
+    pub fn div(a: i64, b: i64) -> PyResult<i64> {
+        a.checked_div(b)
+            .ok_or_else(|| PyZeroDivisionError::new_err("i24 division by zero"))
+    }
+
+    pub fn rem(a: i64, b: i64) -> PyResult<i64> {
+        a.checked_rem(b)
+            .ok_or_else(|| PyZeroDivisionError::new_err("i24 modulo by zero"))
+    }
+
+    pub fn floor_div(a: i64, b: i64) -> PyResult<i64> {
+        let q = div(a, b)?;
+        Ok(if a % b != 0 && (a < 0) != (b < 0) {
+            q - 1
+        } else {
+            q
+        })
+    }
+
+    pub fn modulo(a: i64, b: i64) -> PyResult<i64> {
+        let r = rem(a, b)?;
+        Ok(if r != 0 && (r < 0) != (b < 0) {
+            r + b
+        } else {
+            r
+        })
+    }
+
+    // HVM has no integer power, so this is the power computed with the
+    // same wrapping multiplication. Negative exponents give the truncated
+    // result, like the power compiled for Bend.
+    pub fn pow(a: i64, b: i64) -> PyResult<i64> {
+        let Ok(exp) = u32::try_from(b) else {
+            return match a {
+                0 => Err(PyZeroDivisionError::new_err(
+                    "i24 power of zero with a negative exponent",
+                )),
+                1 => Ok(1),
+                -1 if b % 2 == 0 => Ok(1),
+                -1 => Ok(-1),
+                _ => Ok(0),
+            };
+        };
+
+        Ok(match a.checked_pow(exp) {
+            Some(val) => val,
+            // The lower bits are still right, but the result must be kept
+            // out of the 24-bit range to be detected as an overflow.
+            None => match a.wrapping_pow(exp) {
+                val if super::i24::fits(val) => val + (1 << 40),
+                val => val,
+            },
+        })
+    }
+
+    // HVM has no shifts for signed numbers, these are the shifts of the
+    // two's complement, keeping the sign on right shifts.
+    pub fn shl(a: i64, b: i64) -> PyResult<i64> {
+        Ok(a << (b & 31))
+    }
+
+    pub fn shr(a: i64, b: i64) -> PyResult<i64> {
+        Ok(a >> (b & 31))
     }
 }
 
-// TODO: Check for overflow on the operations
-// TODO: Implement tests for each operation comparing to Bend
+impl i24 {
+    // Extracts an i24 or an int operand, other values can't be operands.
+    fn operand(&self, other: &Bound<'_, PyAny>) -> PyResult<Option<Self>> {
+        if let Ok(other) = other.extract::<Self>() {
+            return Ok(Some(other));
+        }
+
+        match other.downcast::<PyLong>() {
+            Ok(other) => Ok(Some(Self::from_int(other, self.1)?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    // Applies the operation when the other value is an i24 or an int, and
+    // falls back to the Python operation on ints for floats.
+    fn binary(
+        &self,
+        other: &Bound<'_, PyAny>,
+        name: &str,
+        reflected: bool,
+        op: impl Fn(i64, i64) -> PyResult<i64>,
+    ) -> PyResult<PyObject> {
+        let py = other.py();
+
+        if let Some(other) = self.operand(other)? {
+            let (a, b) = if reflected {
+                (other, *self)
+            } else {
+                (*self, other)
+            };
+            let result = op(a.0 as i64, b.0 as i64)?;
+            return Ok(Self::from_result(result, a.1 || b.1)?.into_py(py));
+        }
+
+        if other.is_instance_of::<PyFloat>() {
+            return match reflected {
+                true => py_operator(py, name, other, self.0),
+                false => py_operator(py, name, self.0, other),
+            };
+        }
+
+        Ok(py.NotImplemented())
+    }
+}
 
 #[pymethods]
 impl i24 {
     #[new]
-    fn new_py(value: i32) -> Self {
-        i24::new(value)
+    #[pyo3(signature = (value, checked = false))]
+    fn new_py(value: &Bound<'_, PyLong>, checked: bool) -> PyResult<Self> {
+        Self::from_int(value, checked)
+    }
+
+    #[getter]
+    fn checked(&self) -> bool {
+        self.1
+    }
+
+    fn __add__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "add", false, |a, b| Ok(a + b))
     }
 
-    fn __add__(&self, other: &Self) -> Self {
-        i24::add(*self, *other)
+    fn __radd__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "add", true, |a, b| Ok(a + b))
     }
 
-    fn __sub__(&self, other: &Self) -> Self {
-        i24::sub(*self, *other)
+    fn __sub__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "sub", false, |a, b| Ok(a - b))
+    }
+
+    fn __rsub__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "sub", true, |a, b| Ok(a - b))
+    }
+
+    fn __mul__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "mul", false, |a, b| Ok(a * b))
+    }
+
+    fn __rmul__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "mul", true, |a, b| Ok(a * b))
+    }
+
+    // Division in HVM is always an integer division.
+    fn __truediv__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "truediv", false, op::div)
+    }
+
+    fn __rtruediv__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "truediv", true, op::div)
+    }
+
+    fn __floordiv__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "floordiv", false, op::floor_div)
+    }
+
+    fn __rfloordiv__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "floordiv", true, op::floor_div)
+    }
+
+    fn __mod__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "mod", false, op::modulo)
+    }
+
+    fn __rmod__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "mod", true, op::modulo)
+    }
+
+    fn __pow__(
+        &self,
+        other: &Bound<'_, PyAny>,
+        modulo: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        if modulo.is_some() {
+            return Err(PyValueError::new_err("i24 pow() does not support a modulo"));
+        }
+        self.binary(other, "pow", false, op::pow)
+    }
+
+    fn __rpow__(
+        &self,
+        other: &Bound<'_, PyAny>,
+        modulo: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        if modulo.is_some() {
+            return Err(PyValueError::new_err("i24 pow() does not support a modulo"));
+        }
+        self.binary(other, "pow", true, op::pow)
+    }
+
+    fn __lshift__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "lshift", false, op::shl)
+    }
+
+    fn __rlshift__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "lshift", true, op::shl)
+    }
+
+    fn __rshift__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "rshift", false, op::shr)
+    }
+
+    fn __rrshift__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "rshift", true, op::shr)
+    }
+
+    fn __and__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "and_", false, |a, b| Ok(a & b))
+    }
+
+    fn __rand__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "and_", true, |a, b| Ok(a & b))
+    }
+
+    fn __or__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "or_", false, |a, b| Ok(a | b))
+    }
+
+    fn __ror__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "or_", true, |a, b| Ok(a | b))
+    }
+
+    fn __xor__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "xor", false, |a, b| Ok(a ^ b))
+    }
+
+    fn __rxor__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "xor", true, |a, b| Ok(a ^ b))
+    }
+
+    fn __neg__(&self) -> PyResult<Self> {
+        Self::from_result(-(self.0 as i64), self.1)
+    }
+
+    fn __pos__(&self) -> Self {
+        *self
+    }
+
+    fn __abs__(&self) -> PyResult<Self> {
+        Self::from_result((self.0 as i64).abs(), self.1)
+    }
+
+    fn __invert__(&self) -> Self {
+        Self(!self.0, self.1)
+    }
+
+    // Compares by value with i24s and with any Python number.
+    fn __richcmp__(&self, other: &Bound<'_, PyAny>, op: CompareOp) -> PyResult<PyObject> {
+        let py = other.py();
+        let other = match other.extract::<Self>() {
+            Ok(other) => other.0.into_py(py),
+            Err(_) => other.clone().unbind(),
+        };
+
+        let name = match op {
+            CompareOp::Lt => "lt",
+            CompareOp::Le => "le",
+            CompareOp::Eq => "eq",
+            CompareOp::Ne => "ne",
+            CompareOp::Gt => "gt",
+            CompareOp::Ge => "ge",
+        };
+
+        py_operator(py, name, self.0, other)
+    }
+
+    // Equal to the hash of the int with the same value, since they compare equal.
+    fn __hash__(&self, py: Python<'_>) -> PyResult<isize> {
+        self.0.into_py(py).bind(py).hash()
+    }
+
+    fn __bool__(&self) -> bool {
+        self.0 != 0
+    }
+
+    fn __int__(&self) -> i32 {
+        self.0
+    }
+
+    fn __index__(&self) -> i32 {
+        self.0
+    }
+
+    fn __float__(&self) -> f64 {
+        self.0 as f64
+    }
+
+    fn __repr__(&self) -> String {
+        match self.1 {
+            true => format!("i24({}, checked=True)", self.0),
+            false => format!("i24({})", self.0),
+        }
     }
 
     fn __str__(&self) -> String {
         self.0.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use pyo3::{exceptions::PyOverflowError, prelude::*, types::PyLong};
+
+    use super::i24;
+
+    #[test]
+    fn wraps_around_24_bits() {
+        assert_eq!(i24::new(i24::MAX as i32 + 1).get(), i24::MIN as i32);
+        assert_eq!(i24::new(i24::MIN as i32 - 1).get(), i24::MAX as i32);
+        assert_eq!(i24::new(0xffffff).get(), -1);
+        assert_eq!(i24::new(-1).get(), -1);
+        assert_eq!(i24::new(1 << 24).get(), 0);
+    }
+
+    #[test]
+    fn checked_values_raise_on_overflow() {
+        pyo3::prepare_freethreaded_python();
+
+        Python::with_gil(|py| {
+            let int = |code: &str| {
+                py.eval_bound(code, None, None)
+                    .unwrap()
+                    .downcast_into::<PyLong>()
+                    .unwrap()
+            };
+
+            let above = int("2**23");
+            assert_eq!(i24::from_int(&above, false).unwrap().get(), i24::MIN as i32);
+            let err = i24::from_int(&above, true).unwrap_err();
+            assert!(err.is_instance_of::<PyOverflowError>(py));
+
+            // Ints that don't fit in 64 bits are wrapped too.
+            assert_eq!(i24::from_int(&int("2**70 + 5"), false).unwrap().get(), 5);
+            assert!(i24::from_int(&int("-2**70"), true).is_err());
+
+            let max = int(&i24::MAX.to_string());
+            let wrapped = Py::new(py, i24::from_int(&max, false).unwrap()).unwrap();
+            let sum = wrapped.bind(py).call_method1("__add__", (1,)).unwrap();
+            assert_eq!(sum.extract::<i24>().unwrap().get(), i24::MIN as i32);
+
+            let checked = Py::new(py, i24::from_int(&max, true).unwrap()).unwrap();
+            let err = checked.bind(py).call_method1("__add__", (1,)).unwrap_err();
+            assert!(err.is_instance_of::<PyOverflowError>(py));
+        });
+    }
+}
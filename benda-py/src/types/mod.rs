@@ -1,18 +1,28 @@
-use core::panic;
+use std::collections::HashMap;
 
-use bend::{fun::Num, imp};
+use bend::{
+    fun::{self, Book, Num, STRINGS},
+    imp,
+};
 
 use pyo3::{
     exceptions::PyTypeError,
-    types::{PyAnyMethods, PyFloat, PyTypeMethods},
-    Bound, FromPyObject, PyAny, PyErr, PyTypeCheck,
+    types::{PyAnyMethods, PyFloat, PyFunction, PyTuple, PyTupleMethods, PyTypeMethods},
+    Bound, FromPyObject, IntoPy, PyAny, PyErr, PyObject, PyResult, PyTypeCheck, Python,
 };
-use tree::{Leaf, Node, Tree};
+use user_adt::{extract_bend_method, extract_user_adt};
 
+pub mod bignum;
+pub mod enums;
 pub mod f24;
 pub mod i24;
+pub mod modules;
+pub mod names;
+pub mod overflow;
+pub mod readback;
 pub mod tree;
 pub mod u24;
+pub mod user_adt;
 
 pub trait BendType {
     fn to_bend(&self) -> ToBendResult;
@@ -31,51 +41,300 @@ pub fn extract_inner<'py, T: BendType + PyTypeCheck + FromPyObject<'py>>(
     None
 }
 
+// Applies a function of Python's `operator` module to plain Python values.
+// Used by the numeric types for operations with values of other types.
+pub fn py_operator(
+    py: Python<'_>,
+    name: &str,
+    a: impl IntoPy<PyObject>,
+    b: impl IntoPy<PyObject>,
+) -> PyResult<PyObject> {
+    let operator = py.import_bound("operator")?.getattr(name)?;
+    Ok(operator.call1((a.into_py(py), b.into_py(py)))?.unbind())
+}
+
 pub fn extract_num(arg: Bound<PyAny>, t_type: BuiltinType) -> ToBendResult {
     match t_type {
-        BuiltinType::I32 => arg.to_string().parse::<i32>().unwrap().to_bend(),
+        BuiltinType::I32 => overflow::check_int(arg.downcast()?)?.to_bend(),
         BuiltinType::F32 => arg.to_string().parse::<f32>().unwrap().to_bend(),
         _ => unreachable!(),
     }
 }
 
 pub fn extract_type(arg: Bound<PyAny>) -> ToBendResult {
+    if let Some(term) = extract_inner::<user_adt::Term>(arg.clone()) {
+        return term.to_bend();
+    }
+
+    if let Some(val) = extract_bend_method(&arg) {
+        return val;
+    }
+
+    if let Some(val) = extract_user_adt(&arg) {
+        return val;
+    }
+
+    if let Some(val) = enums::extract_enum(&arg) {
+        return val;
+    }
+
+    if let Some(val) = extract_function(&arg) {
+        return val;
+    }
+
+    // `None`, like the empty fields of `Optional` annotations, is erased.
+    if arg.is_none() {
+        return Ok(imp::Expr::Era);
+    }
+
     let t_type = arg.get_type();
     let name = t_type.name().unwrap();
 
-    let arg_type = BuiltinType::from(name.to_string());
+    let arg_type = BuiltinType::try_from(name.to_string())?;
 
     match arg_type {
-        BuiltinType::U24 => extract_inner::<crate::u24>(arg).unwrap().to_bend(),
+        BuiltinType::U24 => extract_inner::<u24::u24>(arg).unwrap().to_bend(),
+        BuiltinType::I24 => extract_inner::<i24::i24>(arg).unwrap().to_bend(),
+        BuiltinType::F24 => extract_inner::<f24::f24>(arg).unwrap().to_bend(),
+        BuiltinType::Str => arg.extract::<String>()?.to_bend(),
+        BuiltinType::I32 if bignum::enabled(arg.py()) => bignum::to_bend(arg.downcast()?),
         BuiltinType::I32 => extract_num(arg, BuiltinType::I32),
         BuiltinType::F32 => extract_num(arg, BuiltinType::F32),
-        BuiltinType::Tree => extract_inner::<Tree>(arg).unwrap().to_bend(),
-        BuiltinType::Node => extract_inner::<Node>(arg).unwrap().to_bend(),
-        BuiltinType::Leaf => extract_inner::<Leaf>(arg).unwrap().to_bend(),
+        BuiltinType::Tree | BuiltinType::Node | BuiltinType::Leaf => tree::tree_to_bend(&arg),
+        BuiltinType::Tuple => Ok(imp::Expr::Tup {
+            els: arg
+                .downcast::<PyTuple>()?
+                .iter()
+                .map(extract_type)
+                .collect::<PyResult<_>>()?,
+        }),
+    }
+}
+
+// `@bjit` functions are compiled with the rest of their module, so they
+// are given to Bend as a reference to their definition.
+fn extract_function(arg: &Bound<PyAny>) -> Option<ToBendResult> {
+    if arg.get_type().name().is_ok_and(|name| name == "benda.bjit") {
+        let name = arg
+            .getattr("__wrapped__")
+            .and_then(|fun| modules::bend_name(&fun));
+        return Some(name.map(|name| imp::Expr::Var {
+            nam: fun::Name::new(name),
+        }));
+    }
+
+    if arg.is_instance_of::<PyFunction>() {
+        let name = arg.getattr("__name__").ok()?;
+        return Some(Err(PyTypeError::new_err(format!(
+            "The function '{}' must be decorated with @bjit to be given to Bend",
+            name
+        ))));
+    }
+
+    None
+}
+
+// Converts the value of an argument into a Bend term. Values may be very deep,
+// so this uses an explicit stack instead of the recursive `imp::Expr::to_fun`.
+// `field_adts` has the datatype of the fields annotated with a class, which
+// the values given to them are built for.
+#[allow(clippy::mutable_key_type)]
+pub fn value_to_fun(
+    expr: imp::Expr,
+    book: &Book,
+    ctr_aliases: &HashMap<fun::Name, fun::Name>,
+    field_adts: &HashMap<fun::Name, Vec<Option<fun::Name>>>,
+) -> Result<fun::Term, String> {
+    enum Work {
+        Convert(imp::Expr),
+        Done(fun::Term),
+        Apply(usize),
+        List(usize),
+        Tup(usize),
+    }
+
+    let mut work: Vec<Work> = vec![Work::Convert(expr)];
+    let mut done: Vec<fun::Term> = vec![];
+
+    while let Some(item) = work.pop() {
+        match item {
+            Work::Convert(expr) => match expr {
+                imp::Expr::Era => done.push(fun::Term::Era),
+                imp::Expr::Var { nam } => done.push(fun::Term::Var { nam }),
+                imp::Expr::Num { val } => done.push(fun::Term::Num { val }),
+                imp::Expr::Str { val } => done.push(fun::Term::Str { val }),
+                imp::Expr::Ctr { name, args, kwargs } if kwargs.is_empty() => {
+                    let nam = class_ctr(ctr_aliases, &name).unwrap_or(name);
+                    let adts = field_adts.get(&nam);
+
+                    work.push(Work::Apply(args.len()));
+                    for (index, mut arg) in args.into_iter().enumerate().rev() {
+                        if let Some(adt) = adts.and_then(|adts| adts.get(index)?.as_ref()) {
+                            fit_value(book, ctr_aliases, adt, &mut arg);
+                        }
+                        work.push(Work::Convert(arg));
+                    }
+                    work.push(Work::Done(fun::Term::Ref { nam }));
+                }
+                imp::Expr::Call { fun, args, kwargs } if kwargs.is_empty() => {
+                    work.push(Work::Apply(args.len()));
+                    work.extend(args.into_iter().rev().map(Work::Convert));
+                    work.push(Work::Convert(*fun));
+                }
+                imp::Expr::Lst { els } => {
+                    work.push(Work::List(els.len()));
+                    work.extend(els.into_iter().rev().map(Work::Convert));
+                }
+                imp::Expr::Tup { els } => {
+                    work.push(Work::Tup(els.len()));
+                    work.extend(els.into_iter().rev().map(Work::Convert));
+                }
+                // Other expressions only come from `benda.Term` and are small,
+                // so they can be converted by Bend, after ordering their kwargs.
+                expr => {
+                    let mut def = imp::Definition {
+                        name: fun::Name::new("value"),
+                        params: vec![],
+                        body: imp::Stmt::Return {
+                            term: Box::new(expr),
+                        },
+                    };
+                    def.order_kwargs(book)?;
+
+                    let mut def = def.to_fun(false)?;
+                    done.push(std::mem::take(&mut def.rules[0].body));
+                }
+            },
+            Work::Done(term) => done.push(term),
+            Work::Apply(len) => {
+                let args = done.split_off(done.len() - len);
+                let fun = done.pop().unwrap();
+                done.push(fun::Term::call(fun, args));
+            }
+            Work::List(len) => {
+                let els = done.split_off(done.len() - len);
+                done.push(fun::Term::List { els });
+            }
+            Work::Tup(len) => {
+                let els = done.split_off(done.len() - len);
+                done.push(fun::Term::Fan {
+                    fan: fun::FanKind::Tup,
+                    tag: fun::Tag::Static,
+                    els,
+                });
+            }
+        }
+    }
+
+    Ok(done.pop().unwrap())
+}
+
+// The constructor a class is built with when it is part of only one union,
+// like `Shape/Circle` for `Circle`. `ctr_aliases` maps the constructors of
+// the unions to their class, and the other classes have their own.
+#[allow(clippy::mutable_key_type)]
+pub fn class_ctr(
+    ctr_aliases: &HashMap<fun::Name, fun::Name>,
+    class: &fun::Name,
+) -> Option<fun::Name> {
+    let mut ctrs = ctr_aliases
+        .iter()
+        .filter(|(_, member)| *member == class)
+        .map(|(ctr, _)| ctr);
+
+    match (ctrs.next(), ctrs.next()) {
+        (Some(ctr), None) => Some(ctr.clone()),
+        _ => None,
+    }
+}
+
+// The datatype of the values of a class or union.
+#[allow(clippy::mutable_key_type)]
+pub fn class_adt(
+    book: &Book,
+    ctr_aliases: &HashMap<fun::Name, fun::Name>,
+    class: &fun::Name,
+) -> Option<fun::Name> {
+    match class_ctr(ctr_aliases, class) {
+        Some(ctr) => book.ctrs.get(&ctr).cloned(),
+        None => book.adts.contains_key(class).then(|| class.clone()),
+    }
+}
+
+// Builds a value given where a datatype is expected with its constructors:
+// `None` with its `None` constructor, like `Node/None`, and a class of many
+// unions with the constructor of the expected one.
+#[allow(clippy::mutable_key_type)]
+pub fn fit_value(
+    book: &Book,
+    ctr_aliases: &HashMap<fun::Name, fun::Name>,
+    adt: &fun::Name,
+    value: &mut imp::Expr,
+) {
+    let Some(ctrs) = book.adts.get(adt).map(|adt| &adt.ctrs) else {
+        return;
+    };
+
+    match value {
+        imp::Expr::Era => {
+            let none = fun::Name::new(format!("{}/None", adt));
+            if ctrs.contains_key(&none) {
+                *value = imp::Expr::Ctr {
+                    name: none,
+                    args: vec![],
+                    kwargs: vec![],
+                };
+            }
+        }
+        imp::Expr::Ctr { name, .. } if !ctrs.contains_key(name) => {
+            let class = ctr_aliases.get(name).unwrap_or(name);
+            if let Some(ctr) = ctrs
+                .keys()
+                .find(|ctr| ctr_aliases.get(*ctr).unwrap_or(ctr) == class)
+            {
+                *name = ctr.clone();
+            }
+        }
+        _ => {}
     }
 }
 
 #[derive(Debug)]
 pub enum BuiltinType {
     U24,
+    I24,
+    F24,
+    Str,
     F32,
     I32,
     Tree,
     Leaf,
     Node,
+    Tuple,
 }
 
-impl From<String> for BuiltinType {
-    fn from(value: String) -> Self {
-        match value.as_str() {
+impl TryFrom<String> for BuiltinType {
+    type Error = PyErr;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Ok(match value.as_str() {
             "float" => BuiltinType::F32,
             "int" => BuiltinType::I32,
+            "str" => BuiltinType::Str,
+            "tuple" => BuiltinType::Tuple,
             "benda.u24" => BuiltinType::U24,
+            "benda.i24" => BuiltinType::I24,
+            "benda.f24" => BuiltinType::F24,
             "benda.Node" => BuiltinType::Node,
             "benda.Leaf" => BuiltinType::Leaf,
             "benda.Tree" => BuiltinType::Tree,
-            _ => panic!("Unsupported argument type"),
-        }
+            _ => {
+                return Err(PyTypeError::new_err(format!(
+                    "Values of type '{}' can't be given to Bend",
+                    value
+                )))
+            }
+        })
     }
 }
 
@@ -103,6 +362,14 @@ impl BendType for i32 {
     }
 }
 
+impl BendType for String {
+    fn to_bend(&self) -> ToBendResult {
+        Ok(imp::Expr::Str {
+            val: STRINGS.get(self.as_str()),
+        })
+    }
+}
+
 impl BendType for PyFloat {
     fn to_bend(&self) -> ToBendResult {
         let num: Result<f32, PyErr> = self.extract();
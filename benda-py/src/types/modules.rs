@@ -0,0 +1,47 @@
+use std::cell::RefCell;
+
+use pyo3::{prelude::*, sync::GILProtected, types::PyDict};
+
+use super::names::{escape, unescape};
+
+// The module of the function being called. Its classes and functions keep
+// their names in Bend, while the ones of the other modules it imports are
+// prefixed with their module, like `shapes/Circle`, as the parser names them.
+static MAIN_MODULE: GILProtected<RefCell<String>> = GILProtected::new(RefCell::new(String::new()));
+
+// Returns the module that was set before, to put it back.
+pub fn set_main_module(py: Python<'_>, module: &str) -> String {
+    MAIN_MODULE.get(py).replace(module.to_string())
+}
+
+// The prefix of the Bend names of a module, like `pkg/shapes/` for `pkg.shapes`.
+pub fn prefix(py: Python<'_>, module: &str) -> String {
+    if *MAIN_MODULE.get(py).borrow() == module {
+        String::new()
+    } else {
+        let parts: Vec<String> = module.split('.').map(escape).collect();
+        format!("{}/", parts.join("/"))
+    }
+}
+
+// The Bend name of a class or function, from its name and module.
+pub fn bend_name(obj: &Bound<'_, PyAny>) -> PyResult<String> {
+    let name = obj.getattr("__name__")?.to_string();
+    let module = obj.getattr("__module__")?.to_string();
+    Ok(format!("{}{}", prefix(obj.py(), &module), escape(&name)))
+}
+
+// Finds the class of a constructor named after its module, like
+// `pkg/shapes/Circle`, or an alias of it, like `shapes/Shape/Circle`.
+pub fn find_module_class<'py>(py: Python<'py>, ctr: &str) -> Option<Bound<'py, PyAny>> {
+    let parts: Vec<String> = ctr.split('/').map(unescape).collect();
+    let (class_name, path) = parts.split_last()?;
+
+    let modules = py.import_bound("sys").ok()?.getattr("modules").ok()?;
+    let modules = modules.downcast_into::<PyDict>().ok()?;
+
+    (1..=path.len()).rev().find_map(|len| {
+        let module = modules.get_item(path[..len].join(".")).ok()??;
+        module.getattr(class_name.as_str()).ok()
+    })
+}
@@ -0,0 +1,125 @@
+use std::{collections::HashSet, fmt::Write, sync::OnceLock};
+
+use bend::fun::Book;
+
+// Words with a meaning in Bend programs. They don't break the definitions
+// built by the parser, but they would break the Bend code printed from them.
+const KEYWORDS: [&str; 18] = [
+    "ask", "bend", "case", "def", "do", "else", "fold", "if", "lambda", "let", "match", "object",
+    "open", "return", "switch", "type", "use", "with",
+];
+
+// The names Python identifiers can't take in Bend: the keywords, the
+// entrypoint and the ones of the builtin definitions and datatypes, like
+// `List` or `print`, which would be replaced or redefined.
+fn reserved() -> &'static HashSet<String> {
+    static RESERVED: OnceLock<HashSet<String>> = OnceLock::new();
+
+    RESERVED.get_or_init(|| {
+        let book = Book::builtins();
+        let builtins = book
+            .defs
+            .keys()
+            .chain(book.adts.keys())
+            .chain(book.ctrs.keys())
+            .filter_map(|name| name.split('/').next());
+
+        KEYWORDS
+            .into_iter()
+            .chain(["main"])
+            .chain(builtins)
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+// The Bend name of a Python identifier. HVM only takes ASCII names, so other
+// characters are written as their code point between dashes, like `caf-e9-`
+// for `café`, and the reserved names get a dash at the end, like `main-`.
+// Python identifiers have no dashes, so escaped names never clash with them.
+pub fn escape(name: &str) -> String {
+    if name.is_ascii() {
+        return match reserved().contains(name) {
+            true => format!("{}-", name),
+            false => name.to_string(),
+        };
+    }
+
+    let mut escaped = String::new();
+    for c in name.chars() {
+        match c.is_ascii() {
+            true => escaped.push(c),
+            false => write!(escaped, "-{:x}-", c as u32).unwrap(),
+        }
+    }
+    escaped
+}
+
+// The Python identifier of an escaped name, for messages and readback.
+// Names made of many parts, like `shapes/Circle`, are unescaped by part.
+pub fn unescape(name: &str) -> String {
+    let mut unescaped = String::new();
+    let mut rest = name;
+
+    while let Some(start) = rest.find('-') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        let code = rest
+            .find('-')
+            .and_then(|end| Some((end, u32::from_str_radix(&rest[..end], 16).ok()?)))
+            .and_then(|(end, code)| Some((end, char::from_u32(code)?)));
+
+        // A dash not starting an escaped character ends a reserved name.
+        if let Some((end, c)) = code {
+            unescaped.push(c);
+            rest = &rest[end + 1..];
+        }
+    }
+
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, unescape};
+
+    #[test]
+    fn escaped_names_are_ascii_and_unescaped_back() {
+        let names = [
+            "x",
+            "x_1",
+            "café",
+            "naïve_λ",
+            "ñandú",
+            "main",
+            "fold",
+            "open",
+            "switch",
+            "List",
+            "print",
+            "Tree",
+        ];
+
+        for name in names {
+            let escaped = escape(name);
+            assert!(escaped.is_ascii(), "{} escaped as {}", name, escaped);
+            assert_eq!(unescape(&escaped), name);
+        }
+    }
+
+    #[test]
+    fn only_reserved_and_non_ascii_names_are_changed() {
+        assert_eq!(escape("area"), "area");
+        assert_eq!(escape("main"), "main-");
+        assert_eq!(escape("List"), "List-");
+        assert_eq!(escape("café"), "caf-e9-");
+    }
+
+    #[test]
+    fn names_of_many_parts_are_unescaped_by_part() {
+        let name = format!("{}/{}", escape("géo"), escape("match"));
+        assert_eq!(unescape(&name), "géo/match");
+    }
+}
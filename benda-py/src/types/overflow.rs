@@ -0,0 +1,108 @@
+use std::cell::RefCell;
+
+use pyo3::{
+    exceptions::{PyOverflowError, PyRuntimeWarning, PyValueError},
+    prelude::*,
+    sync::GILProtected,
+    types::PyLong,
+};
+
+use super::i24::i24;
+
+// What to do with a Python int that doesn't fit in the 24 bits of HVM,
+// chosen with `@bjit(overflow=...)`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Overflow {
+    #[default]
+    Error,
+    Warn,
+    Wrap,
+}
+
+impl<'py> FromPyObject<'py> for Overflow {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match ob.extract::<String>()?.as_str() {
+            "error" => Ok(Overflow::Error),
+            "warn" => Ok(Overflow::Warn),
+            "wrap" => Ok(Overflow::Wrap),
+            other => Err(PyValueError::new_err(format!(
+                "overflow must be 'error', 'warn' or 'wrap', not '{}'",
+                other
+            ))),
+        }
+    }
+}
+
+// The policy of the function being called and the argument being converted,
+// to name it in the messages. Ints may be deep inside the argument, in a
+// dataclass or a tree, so they are kept here instead of passed around.
+struct State {
+    policy: Overflow,
+    argument: String,
+}
+
+static STATE: GILProtected<RefCell<State>> = GILProtected::new(RefCell::new(State {
+    policy: Overflow::Error,
+    argument: String::new(),
+}));
+
+// The setters return the previous value, to put it back.
+pub fn set_policy(py: Python<'_>, policy: Overflow) -> Overflow {
+    std::mem::replace(&mut STATE.get(py).borrow_mut().policy, policy)
+}
+
+pub fn set_argument(py: Python<'_>, argument: &str) -> String {
+    std::mem::replace(
+        &mut STATE.get(py).borrow_mut().argument,
+        argument.to_string(),
+    )
+}
+
+// Converts a Python int into an i24, following the policy when it doesn't fit.
+pub fn check_int(value: &Bound<'_, PyLong>) -> PyResult<i32> {
+    let py = value.py();
+    let wrapped = i24::from_int(value, false)?.get();
+
+    if value.extract::<i32>().is_ok_and(|val| val == wrapped) {
+        return Ok(wrapped);
+    }
+
+    let (policy, argument) = {
+        let state = STATE.get(py).borrow();
+        (state.policy, state.argument.clone())
+    };
+
+    let message = format!(
+        "argument '{}' is {}, which does not fit in the 24 bits of HVM (the range is [{}, {}])",
+        argument,
+        value,
+        i24::MIN,
+        i24::MAX
+    );
+
+    match policy {
+        Overflow::Error => Err(PyOverflowError::new_err(message)),
+        Overflow::Warn => {
+            let message = format!("{}, it was wrapped to {}", message, wrapped);
+            let warning = py.get_type_bound::<PyRuntimeWarning>();
+            PyErr::warn_bound(py, &warning, &message, 1)?;
+            Ok(wrapped)
+        }
+        Overflow::Wrap => Ok(wrapped),
+    }
+}
+
+// Follows the policy for a number literal of the Python code that doesn't fit.
+pub fn check_literal(policy: Overflow, literal: &str) -> PyResult<()> {
+    let message = format!("the literal {} does not fit in the 24 bits of HVM", literal);
+
+    match policy {
+        Overflow::Error => Err(PyOverflowError::new_err(message)),
+        Overflow::Warn => Python::with_gil(|py| {
+            let message = format!("{}, it will be wrapped", message);
+            let warning = py.get_type_bound::<PyRuntimeWarning>();
+            PyErr::warn_bound(py, &warning, &message, 1)
+        }),
+        Overflow::Wrap => Ok(()),
+    }
+}
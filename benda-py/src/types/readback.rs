@@ -0,0 +1,695 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use bend::fun::{Adt, Book, FanKind, Name, Num, Pattern, Term};
+use pyo3::{
+    exceptions::PyTypeError,
+    prelude::*,
+    types::{PyDict, PyFunction, PyList, PyLong, PyString, PyTuple},
+};
+
+use super::{
+    bignum, class_adt, enums,
+    f24::f24,
+    i24::i24,
+    modules::{bend_name, find_module_class},
+    names::{escape, unescape},
+    u24::u24,
+    user_adt::{find_user_class, from_bend},
+};
+
+// Converts the Term returned by HVM back into Python objects.
+// Constructors are mapped to the Python classes they were created from,
+// looking them up in the globals of the module of the annotated function.
+pub struct Readback<'py, 'b> {
+    py: Python<'py>,
+    book: &'b Book,
+    // The constructors of the unions, with the class each one is built from,
+    // like `Circle` for `Shape/Circle`.
+    ctr_aliases: &'b HashMap<Name, Name>,
+    globals: Bound<'py, PyDict>,
+    ret_type: Option<Bound<'py, PyAny>>,
+    // The type the parser inferred for the returned value.
+    ret_hint: Option<Hint>,
+    ctr_classes: RefCell<HashMap<Name, Option<CtrClass<'py>>>>,
+}
+
+// The Python class of a constructor and the expected type of its fields.
+type CtrClass<'py> = (Bound<'py, PyAny>, Vec<Option<Hint>>);
+
+// The expected type of a term, taken from the Python annotations or, for
+// the returned value, inferred by the parser.
+#[derive(Clone, PartialEq)]
+pub enum Hint {
+    Adt(Name),
+    U24,
+    I24,
+    F24,
+    Big,
+    // The hints of the elements of a tuple, from `tuple[...]`.
+    Tup(Vec<Option<Hint>>),
+}
+
+impl Hint {
+    fn adt(&self) -> Option<&Name> {
+        match self {
+            Hint::Adt(adt) => Some(adt),
+            _ => None,
+        }
+    }
+}
+
+impl<'py, 'b> Readback<'py, 'b> {
+    #[allow(clippy::mutable_key_type)]
+    pub fn new(
+        py: Python<'py>,
+        book: &'b Book,
+        ctr_aliases: &'b HashMap<Name, Name>,
+        fun: &Bound<'py, PyFunction>,
+        ret_hint: Option<Hint>,
+    ) -> PyResult<Self> {
+        let globals = fun.getattr("__globals__")?.downcast_into::<PyDict>()?;
+        let ret_type = fun
+            .getattr("__annotations__")?
+            .downcast_into::<PyDict>()?
+            .get_item("return")?;
+
+        Ok(Self {
+            py,
+            book,
+            ctr_aliases,
+            globals,
+            ret_type,
+            ret_hint,
+            ctr_classes: RefCell::new(HashMap::new()),
+        })
+    }
+
+    // Bignums have the same form as lists, so `bignum` tells when the
+    // returned value is one, even without an annotation.
+    pub fn to_python(&self, term: &Term, bignum: bool) -> PyResult<Py<PyAny>> {
+        let hint = match &self.ret_type {
+            _ if bignum => Some(Hint::Big),
+            Some(ret) => self.find_hint(ret),
+            None => self.ret_hint.clone(),
+        };
+        self.read(term, hint)
+    }
+
+    // Reads the term using an explicit stack, since the returned values may be
+    // too deep to be read recursively.
+    //
+    // The expected type of each term is used when it is known. It is needed
+    // because HVM returns the constructor tags as plain numbers, and to give
+    // back numbers annotated as `benda.u24`, `benda.i24` or `benda.f24`.
+    fn read(&self, term: &Term, hint: Option<Hint>) -> PyResult<Py<PyAny>> {
+        enum Work<'t, 'py> {
+            Read(&'t Term, Option<Hint>),
+            List(usize),
+            Tuple(usize),
+            Ctr(Bound<'py, PyAny>, usize),
+        }
+
+        let mut work: Vec<Work> = vec![Work::Read(term, hint)];
+        let mut done: Vec<Py<PyAny>> = vec![];
+
+        while let Some(item) = work.pop() {
+            match item {
+                Work::Read(term, Some(Hint::Big)) => match bignum::from_bend(term) {
+                    Some(value) => done.push(value?.into_py(self.py)),
+                    None => match self.find_value_def(term) {
+                        Some(body) => work.push(Work::Read(body, Some(Hint::Big))),
+                        None => work.push(Work::Read(term, None)),
+                    },
+                },
+                Work::Read(term, hint) => match term {
+                    Term::Num { val } => done.push(match (val, hint) {
+                        (Num::U24(val), Some(Hint::U24)) => u24::new(*val).into_py(self.py),
+                        (Num::I24(val), Some(Hint::I24)) => i24::new(*val).into_py(self.py),
+                        (Num::F24(val), Some(Hint::F24)) => f24::new(*val).into_py(self.py),
+                        (Num::U24(val), _) => val.into_py(self.py),
+                        (Num::I24(val), _) => val.into_py(self.py),
+                        (Num::F24(val), _) => val.into_py(self.py),
+                    }),
+                    Term::Str { val } => done.push(val.to_string().into_py(self.py)),
+                    Term::Era => done.push(self.py.None()),
+                    Term::List { els } => {
+                        work.push(Work::List(els.len()));
+                        work.extend(els.iter().rev().map(|el| Work::Read(el, None)));
+                    }
+                    Term::Fan {
+                        fan: FanKind::Tup,
+                        els,
+                        ..
+                    } => {
+                        // Without its length, a tuple can't be told apart
+                        // from pairs nested in it.
+                        let (els, mut hints) = match hint {
+                            Some(Hint::Tup(hints)) => (flatten_tuple(els, hints.len()), hints),
+                            _ => (els.iter().collect(), vec![]),
+                        };
+                        hints.resize(els.len(), None);
+
+                        work.push(Work::Tuple(els.len()));
+                        let els = els.iter().zip(hints).rev();
+                        work.extend(els.map(|(el, hint)| Work::Read(el, hint)));
+                    }
+                    _ if hint
+                        .as_ref()
+                        .and_then(Hint::adt)
+                        .is_some_and(|adt| adt == "String") =>
+                    {
+                        match self.read_string(term) {
+                            Some(string) => done.push(string.into_py(self.py)),
+                            None => return Err(self.unreadable(term)),
+                        }
+                    }
+                    _ if hint
+                        .as_ref()
+                        .and_then(Hint::adt)
+                        .is_some_and(|adt| adt == "List") =>
+                    {
+                        let Some(els) = self.list_elements(term) else {
+                            return Err(self.unreadable(term));
+                        };
+                        work.push(Work::List(els.len()));
+                        work.extend(els.into_iter().rev().map(|el| Work::Read(el, None)));
+                    }
+                    _ => {
+                        if let Some((ctr, fields)) =
+                            self.find_ctr(term, hint.as_ref().and_then(Hint::adt))
+                        {
+                            // Datatypes of classes annotated as `Optional` get a
+                            // `None` constructor, like `Node/None`.
+                            if fields.is_empty() && ctr.ends_with("/None") {
+                                done.push(self.py.None());
+                                continue;
+                            }
+                            match self.find_ctr_class(&ctr)? {
+                                Some((class, field_hints)) => {
+                                    work.push(Work::Ctr(class, fields.len()));
+                                    let fields = fields.into_iter().zip(field_hints).rev();
+                                    work.extend(fields.map(|(f, hint)| Work::Read(f, hint)));
+                                }
+                                None => return Err(self.unreadable(term)),
+                            }
+                        } else if let Some(body) = self.find_value_def(term) {
+                            work.push(Work::Read(body, hint));
+                        } else {
+                            return Err(self.unreadable(term));
+                        }
+                    }
+                },
+                Work::List(len) => {
+                    let items = done.split_off(done.len() - len);
+                    done.push(PyList::new_bound(self.py, items).into());
+                }
+                Work::Tuple(len) => {
+                    let items = done.split_off(done.len() - len);
+                    done.push(PyTuple::new_bound(self.py, items).into());
+                }
+                Work::Ctr(class, len) => {
+                    let args = done.split_off(done.len() - len);
+                    done.push(from_bend(&class, PyTuple::new_bound(self.py, args))?);
+                }
+            }
+        }
+
+        Ok(done.pop().unwrap())
+    }
+
+    // Arguments of the annotated function are stored as Bend definitions,
+    // so returning one of them unchanged gives back a reference to it.
+    fn find_value_def(&self, term: &Term) -> Option<&'b Term> {
+        if let Term::Ref { nam } = term {
+            if let Some(def) = self.book.defs.get(nam) {
+                if let [rule] = def.rules.as_slice() {
+                    if rule.pats.is_empty() && !def.builtin {
+                        return Some(&rule.body);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // Recognizes both a num-scott encoded constructor, `λx (x tag f1 .. fn)`,
+    // and a constructor applied to its fields, `(Ctr f1 .. fn)`.
+    fn find_ctr<'t>(&self, term: &'t Term, adt: Option<&Name>) -> Option<(Name, Vec<&'t Term>)> {
+        let (bind, body) = match term {
+            Term::Lam { pat, bod, .. } => match pat.as_ref() {
+                Pattern::Var(Some(bind)) => (Some(bind), bod.as_ref()),
+                _ => return None,
+            },
+            _ => (None, term),
+        };
+
+        let mut args: Vec<&Term> = vec![];
+        let mut head = body;
+        while let Term::App { fun, arg, .. } = head {
+            args.push(arg.as_ref());
+            head = fun.as_ref();
+        }
+        args.reverse();
+
+        let ctr = match (bind, head) {
+            (Some(bind), Term::Var { nam }) if nam == bind => {
+                if args.is_empty() {
+                    return None;
+                }
+
+                match args.remove(0) {
+                    Term::Ref { nam } => Name::new(nam.strip_suffix("/tag")?),
+                    Term::Num { val: Num::U24(tag) } => self.find_tag(*tag, args.len(), adt)?,
+                    _ => return None,
+                }
+            }
+            (None, Term::Var { nam } | Term::Ref { nam }) => nam.clone(),
+            _ => return None,
+        };
+
+        let adt = self.book.adts.get(self.book.ctrs.get(&ctr)?)?;
+
+        if adt.ctrs.get(&ctr)?.len() != args.len() {
+            return None;
+        }
+
+        Some((ctr, args))
+    }
+
+    // Finds the constructor with the given tag and number of fields. Without
+    // an expected type, only an unambiguous user constructor is accepted,
+    // falling back to the built-in Tree used by benda.
+    fn find_tag(&self, tag: u32, arity: usize, adt: Option<&Name>) -> Option<Name> {
+        let ctr_of = |adt: &Name| {
+            let (ctr, fields) = self.book.adts.get(adt)?.ctrs.get_index(tag as usize)?;
+            (fields.len() == arity).then(|| ctr.clone())
+        };
+
+        if let Some(adt) = adt {
+            return ctr_of(adt);
+        }
+
+        let mut found = self
+            .book
+            .adts
+            .iter()
+            .filter(|(_, adt)| !adt.builtin)
+            .filter_map(|(nam, _)| ctr_of(nam));
+
+        match (found.next(), found.next()) {
+            (Some(ctr), None) => Some(ctr),
+            (None, _) => ctr_of(&Name::new("Tree")),
+            _ => None,
+        }
+    }
+
+    // Finds the Python class of a constructor and the expected type of each of
+    // its fields, taken from the class annotations. They are cached, since
+    // the same constructor is usually read many times.
+    fn find_ctr_class(&self, ctr: &Name) -> PyResult<Option<CtrClass<'py>>> {
+        if let Some(found) = self.ctr_classes.borrow().get(ctr) {
+            return Ok(found.clone());
+        }
+
+        let found = match self.find_class(ctr)? {
+            Some(class) => {
+                let adt_name = self.book.ctrs.get(ctr).unwrap();
+                let ctr_fields = &self.book.adts[adt_name].ctrs[ctr];
+                let annotations = class
+                    .getattr("__annotations__")
+                    .ok()
+                    .and_then(|ann| ann.downcast_into::<PyDict>().ok());
+
+                let mut field_hints: Vec<Option<Hint>> = vec![];
+                for ctr_field in ctr_fields {
+                    let annotation = match &annotations {
+                        Some(ann) => ann.get_item(unescape(&ctr_field.nam))?,
+                        None => None,
+                    };
+
+                    field_hints.push(match annotation {
+                        Some(annotation) => self.find_hint(&annotation),
+                        None if ctr_field.rec => Some(Hint::Adt(adt_name.clone())),
+                        None => None,
+                    });
+                }
+
+                Some((class, field_hints))
+            }
+            None => None,
+        };
+
+        self.ctr_classes
+            .borrow_mut()
+            .insert(ctr.clone(), found.clone());
+
+        Ok(found)
+    }
+
+    // User constructors are searched in the registered classes and in the module
+    // globals, while the built-in ones (like Tree/Node) are searched in the benda module.
+    fn find_class(&self, ctr: &Name) -> PyResult<Option<Bound<'py, PyAny>>> {
+        let class_name = unescape(ctr.rsplit('/').next().unwrap_or(ctr));
+
+        let is_builtin = self
+            .book
+            .ctrs
+            .get(ctr)
+            .and_then(|adt| self.book.adts.get(adt))
+            .is_some_and(|adt| adt.builtin);
+
+        if is_builtin {
+            let benda = self.py.import_bound("benda")?;
+            return Ok(benda.getattr(class_name.as_str()).ok());
+        }
+
+        if let Some(member) = self.find_enum_member(ctr)? {
+            return Ok(Some(member));
+        }
+
+        // The constructors of a union are found by the name of their class.
+        let ctr = self.ctr_aliases.get(ctr).unwrap_or(ctr);
+        let class_name = unescape(ctr.rsplit('/').next().unwrap_or(ctr));
+
+        if let Some(class) = find_user_class(self.py, ctr) {
+            return Ok(Some(class));
+        }
+
+        // Constructors of other modules are named after them.
+        if let Some(class) = find_module_class(self.py, ctr) {
+            return Ok(Some(class));
+        }
+
+        self.globals.get_item(class_name)
+    }
+
+    // The member of an enum a constructor is named after, like `Color.RED`
+    // for `Color/RED`. It is given back as is, since it has no fields.
+    fn find_enum_member(&self, ctr: &Name) -> PyResult<Option<Bound<'py, PyAny>>> {
+        let Some(adt) = self.book.ctrs.get(ctr) else {
+            return Ok(None);
+        };
+        let Some(member) = ctr
+            .strip_prefix(adt.as_ref())
+            .and_then(|m| m.strip_prefix('/'))
+        else {
+            return Ok(None);
+        };
+
+        let class = match find_module_class(self.py, adt) {
+            Some(class) => Some(class),
+            None => self.globals.get_item(unescape(adt))?,
+        };
+
+        match class {
+            Some(class) if enums::is_enum(&class) => {
+                Ok(class.getattr(unescape(member).as_str()).ok())
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn find_hint(&self, annotation: &Bound<'py, PyAny>) -> Option<Hint> {
+        if annotation.is(&self.py.get_type_bound::<u24>()) {
+            Some(Hint::U24)
+        } else if annotation.is(&self.py.get_type_bound::<i24>()) {
+            Some(Hint::I24)
+        } else if annotation.is(&self.py.get_type_bound::<f24>()) {
+            Some(Hint::F24)
+        } else if bignum::enabled(self.py) && annotation.is(&self.py.get_type_bound::<PyLong>()) {
+            Some(Hint::Big)
+        } else if let Some(member) = optional_member(annotation) {
+            self.find_hint(&member)
+        } else if let Some(els) = tuple_args(annotation) {
+            Some(Hint::Tup(
+                els.iter().map(|el| self.find_hint(&el)).collect(),
+            ))
+        } else {
+            self.find_adt(annotation).map(Hint::Adt)
+        }
+    }
+
+    // Finds the ADT described by a Python type annotation. Type aliases like
+    // `Shape = Circle | Square` are found by the name they have in the module,
+    // and the class of a single union by the union. Unions without a name,
+    // like `Optional[Shape]`, are the ADT with all of their classes.
+    fn find_adt(&self, annotation: &Bound<'py, PyAny>) -> Option<Name> {
+        if let Some(name) = self.find_datatype(annotation) {
+            return class_adt(self.book, self.ctr_aliases, &name);
+        }
+
+        let classes = union_args(annotation)?
+            .iter()
+            .map(|member| self.find_datatype(member))
+            .collect::<Option<Vec<Name>>>()?;
+        let has_class = |adt: &Adt, class: &Name| {
+            adt.ctrs
+                .keys()
+                .any(|ctr| self.ctr_aliases.get(ctr).unwrap_or(ctr) == class)
+        };
+        self.book
+            .adts
+            .iter()
+            .find(|(_, adt)| classes.iter().all(|class| has_class(adt, class)))
+            .map(|(name, _)| name.clone())
+    }
+
+    fn find_datatype(&self, annotation: &Bound<'py, PyAny>) -> Option<Name> {
+        if let Ok(name) = bend_name(annotation) {
+            let name = Name::new(name);
+            if self.book.adts.contains_key(&name) {
+                return Some(name);
+            }
+        }
+
+        // The alias is looked up first, since its value may be a string too,
+        // like `Shape: TypeAlias = "Circle | Square"`.
+        let name = if let Some((name, _)) = self.globals.iter().find(|(_, val)| val.is(annotation))
+        {
+            name.to_string()
+        } else if let Ok(name) = annotation.downcast::<PyString>() {
+            name.to_string()
+        } else if let Ok(name) = annotation.getattr("__forward_arg__") {
+            // Strings inside of other annotations, like `Optional["Cell"]`,
+            // become a `typing.ForwardRef`.
+            name.to_string()
+        } else {
+            annotation.getattr("__name__").ok()?.to_string()
+        };
+
+        // Classes of the module are escaped, the builtin `Tree` of benda is not.
+        [escape(&name), name]
+            .into_iter()
+            .map(Name::new)
+            .find(|name| self.book.adts.contains_key(name))
+    }
+
+    // Bend strings are lists of characters, `String/Cons` and `String/Nil`.
+    fn read_string(&self, term: &Term) -> Option<String> {
+        let adt = Name::new("String");
+        let mut string = String::new();
+        let mut term = term;
+
+        loop {
+            if let Term::Str { val } = term {
+                string.push_str(val);
+                return Some(string);
+            }
+
+            let (_, fields) = self.find_ctr(term, Some(&adt))?;
+            match fields.as_slice() {
+                [] => return Some(string),
+                [Term::Num { val: Num::U24(c) }, tail] => {
+                    string.push(char::from_u32(*c)?);
+                    term = tail;
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    // The elements of a Bend list, `List/Cons` and `List/Nil`.
+    fn list_elements<'t>(&self, term: &'t Term) -> Option<Vec<&'t Term>> {
+        let adt = Name::new("List");
+        let mut els = vec![];
+        let mut term = term;
+
+        loop {
+            if let Term::List { els: rest } = term {
+                els.extend(rest);
+                return Some(els);
+            }
+
+            let (_, fields) = self.find_ctr(term, Some(&adt))?;
+            match fields.as_slice() {
+                [] => return Some(els),
+                [head, tail] => {
+                    els.push(*head);
+                    term = tail;
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    // A term is never given back as its Bend code, since constructors of
+    // different datatypes can look the same without knowing their type.
+    fn unreadable(&self, term: &Term) -> PyErr {
+        PyTypeError::new_err(format!(
+            "Could not read back the value '{}' returned by Bend. If it is an object, annotate the return type of the function with its class",
+            term
+        ))
+    }
+}
+
+// The type of an `Optional[...]`, or of a union with `None`, besides `None`.
+fn optional_member<'py>(annotation: &Bound<'py, PyAny>) -> Option<Bound<'py, PyAny>> {
+    match union_args(annotation)?.as_slice() {
+        [member] => Some(member.clone()),
+        _ => None,
+    }
+}
+
+// The members of a union other than `None`, like `Circle` and `Square` in
+// `Optional[Circle | Square]`.
+fn union_args<'py>(annotation: &Bound<'py, PyAny>) -> Option<Vec<Bound<'py, PyAny>>> {
+    let py = annotation.py();
+    let typing = py.import_bound("typing").ok()?;
+    let origin = typing.call_method1("get_origin", (annotation,)).ok()?;
+    let union_type = py.import_bound("types").ok()?.getattr("UnionType").ok()?;
+    if !origin.is(&typing.getattr("Union").ok()?) && !origin.is(&union_type) {
+        return None;
+    }
+
+    let none_type = py.None().into_bound(py).get_type();
+    let args = annotation.getattr("__args__").ok()?;
+    let args = args.downcast_into::<PyTuple>().ok()?;
+    Some(args.iter().filter(|arg| !arg.is(&none_type)).collect())
+}
+
+// Bend tuples are pairs, so `(a, b, c)` comes back as `(a, (b, c))`. The
+// nested pairs at the end are taken as elements, up to the length of the
+// tuple.
+fn flatten_tuple(els: &[Term], len: usize) -> Vec<&Term> {
+    let mut flat: Vec<&Term> = els.iter().collect();
+
+    while flat.len() < len {
+        match flat.last().copied() {
+            Some(Term::Fan {
+                fan: FanKind::Tup,
+                els,
+                ..
+            }) => {
+                flat.pop();
+                flat.extend(els);
+            }
+            _ => break,
+        }
+    }
+
+    flat
+}
+
+// The element types of a `tuple[...]` annotation.
+fn tuple_args<'py>(annotation: &Bound<'py, PyAny>) -> Option<Bound<'py, PyTuple>> {
+    let origin = annotation.getattr("__origin__").ok()?;
+    if !origin.is(&annotation.py().get_type_bound::<PyTuple>()) {
+        return None;
+    }
+    annotation.getattr("__args__").ok()?.downcast_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bend::fun::{Adt, Book, CtrField, FanKind, Name, Num, Tag, Term};
+    use indexmap::IndexMap;
+    use pyo3::{prelude::*, types::PyFunction};
+
+    use super::{flatten_tuple, Readback};
+
+    fn num(val: u32) -> Term {
+        Term::Num { val: Num::U24(val) }
+    }
+
+    fn tup(els: Vec<Term>) -> Term {
+        Term::Fan {
+            fan: FanKind::Tup,
+            tag: Tag::Static,
+            els,
+        }
+    }
+
+    #[test]
+    fn tuples_are_flattened_up_to_their_length() {
+        // `(1, 2, 3)` comes back as `(1, (2, 3))`.
+        let els = [num(1), tup(vec![num(2), num(3)])];
+        assert_eq!(flatten_tuple(&els, 3), vec![&num(1), &num(2), &num(3)]);
+
+        // In `(1, (2, 3))` the pair is an element.
+        let pair = tup(vec![num(2), num(3)]);
+        assert_eq!(flatten_tuple(&els, 2), vec![&num(1), &pair]);
+
+        // Only the last element is the rest of the tuple.
+        let first = tup(vec![num(1), num(2)]);
+        let els = [first.clone(), tup(vec![num(3), num(4)])];
+        assert_eq!(flatten_tuple(&els, 3), vec![&first, &num(3), &num(4)]);
+    }
+
+    fn adt(ctrs: &[(&str, usize)]) -> Adt {
+        let ctrs = ctrs
+            .iter()
+            .map(|(ctr, arity)| {
+                let fields = (0..*arity)
+                    .map(|field| CtrField {
+                        nam: Name::new(format!("f{}", field)),
+                        rec: false,
+                    })
+                    .collect();
+                (Name::new(*ctr), fields)
+            })
+            .collect::<IndexMap<_, _>>();
+        Adt {
+            ctrs,
+            builtin: false,
+        }
+    }
+
+    #[test]
+    #[allow(clippy::mutable_key_type)]
+    fn tags_are_only_read_without_a_type_when_unambiguous() {
+        let mut book = Book::default();
+        for (name, adt) in [
+            ("A", adt(&[("A/X", 1), ("A/Y", 0)])),
+            ("B", adt(&[("B/Z", 1)])),
+        ] {
+            for ctr in adt.ctrs.keys() {
+                book.ctrs.insert(ctr.clone(), Name::new(name));
+            }
+            book.adts.insert(Name::new(name), adt);
+        }
+        let ctr_aliases = HashMap::new();
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let fun = py.eval_bound("lambda: None", None, None).unwrap();
+            let fun = fun.downcast::<PyFunction>().unwrap();
+            let readback = Readback::new(py, &book, &ctr_aliases, fun, None).unwrap();
+            let tag = |tag, arity, adt: Option<&str>| {
+                let adt = adt.map(Name::new);
+                readback
+                    .find_tag(tag, arity, adt.as_ref())
+                    .map(|ctr| ctr.to_string())
+            };
+
+            // Both `A/X` and `B/Z` are the first constructor with a field.
+            assert_eq!(tag(0, 1, None), None);
+            assert_eq!(tag(0, 1, Some("A")).as_deref(), Some("A/X"));
+            assert_eq!(tag(0, 1, Some("B")).as_deref(), Some("B/Z"));
+            assert_eq!(tag(1, 0, None).as_deref(), Some("A/Y"));
+            assert_eq!(tag(1, 1, Some("A")), None);
+            assert_eq!(tag(2, 0, None), None);
+        });
+    }
+}
@@ -1,109 +1,169 @@
-use core::panic;
 use std::vec;
 
 use bend::{fun, imp};
 use pyo3::{
+    exceptions::{PyTypeError, PyValueError},
+    prelude::*,
     pyclass, pymethods,
-    types::{PyAnyMethods, PyTuple, PyTypeMethods},
-    Bound,
+    types::{PyIterator, PyList, PyType},
 };
 
-use crate::types::extract_inner;
+use crate::types::extract_type;
 
-use super::{u24::u24, BendType, ToBendResult};
+use super::{BendType, ToBendResult};
 
+// A Leaf can hold any value accepted by `extract_type`,
+// it is only converted when the tree is passed to Bend.
 #[derive(Clone, Debug)]
 #[pyclass(module = "benda", name = "Leaf")]
 pub struct Leaf {
-    pub value: u24,
+    pub value: Py<PyAny>,
 }
 
 impl BendType for Leaf {
     fn to_bend(&self) -> ToBendResult {
-        self.value.to_bend()
+        let value = Python::with_gil(|py| extract_type(self.value.bind(py).clone()))?;
+        Ok(leaf_ctr(value))
     }
 }
 
 #[pymethods]
 impl Leaf {
     #[new]
-    fn __new__(val: u32) -> Self {
-        Self {
-            value: u24::new(val),
+    fn __new__(value: Py<PyAny>) -> Self {
+        Self { value }
+    }
+
+    #[classattr]
+    fn __match_args__() -> (&'static str,) {
+        ("value",)
+    }
+
+    #[getter]
+    fn value(&self, py: Python<'_>) -> Py<PyAny> {
+        self.value.clone_ref(py)
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!("Leaf({})", self.value.bind(py).repr()?))
+    }
+
+    fn __eq__(&self, py: Python<'_>, other: &Bound<'_, PyAny>) -> PyResult<bool> {
+        match other.downcast::<Leaf>() {
+            Ok(other) => self.value.bind(py).eq(other.borrow().value.bind(py)),
+            Err(_) => Ok(false),
         }
     }
+
+    fn __iter__<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyIterator>> {
+        iter_values(slf.as_any())
+    }
+
+    fn depth(slf: &Bound<'_, Self>) -> usize {
+        depth(slf.as_any())
+    }
+
+    fn size(slf: &Bound<'_, Self>) -> usize {
+        size(slf.as_any())
+    }
+
+    fn to_list<'py>(slf: &Bound<'py, Self>) -> Bound<'py, PyList> {
+        to_list(slf.as_any())
+    }
 }
 
 #[derive(Clone, Debug)]
 #[pyclass(module = "benda", name = "Node")]
 pub struct Node {
-    pub left: Option<Box<Tree>>,
-    pub right: Option<Box<Tree>>,
+    // Both subtrees are either a Leaf or a Node.
+    pub left: Py<PyAny>,
+    pub right: Py<PyAny>,
 }
 
 #[pymethods]
 impl Node {
     #[new]
-    #[pyo3(signature = (*py_args))]
-    fn new(py_args: &Bound<'_, PyTuple>) -> Self {
-        let mut trees: Option<Tree> = None;
-
-        for arg in py_args {
-            let t_type = arg.get_type();
-            let name = t_type.name().unwrap();
-
-            let tree_type = TreeType::from(name.to_string());
-
-            let new_tree: Option<Tree> = match tree_type {
-                TreeType::Leaf => extract_inner::<Leaf>(arg).map(|leaf| Tree {
-                    leaf: Some(leaf),
-                    node: None,
-                }),
-                TreeType::Node => extract_inner::<Node>(arg).map(|node| Tree {
-                    leaf: None,
-                    node: Some(node),
-                }),
-                TreeType::Tree => extract_inner::<Tree>(arg),
-            };
-
-            if let Some(new_tree) = new_tree {
-                if let Some(tree) = trees {
-                    return Self {
-                        left: Some(Box::new(tree)),
-                        right: Some(Box::new(new_tree)),
-                    };
-                } else {
-                    trees = Some(new_tree);
-                }
-            }
-        }
+    fn new(left: &Bound<'_, PyAny>, right: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(Self {
+            left: subtree(left)?,
+            right: subtree(right)?,
+        })
+    }
+
+    #[classattr]
+    fn __match_args__() -> (&'static str, &'static str) {
+        ("left", "right")
+    }
+
+    #[getter]
+    fn left(&self, py: Python<'_>) -> Py<PyAny> {
+        self.left.clone_ref(py)
+    }
+
+    #[getter]
+    fn right(&self, py: Python<'_>) -> Py<PyAny> {
+        self.right.clone_ref(py)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+        tree_repr(slf.as_any())
+    }
+
+    fn __eq__(slf: &Bound<'_, Self>, other: &Bound<'_, PyAny>) -> PyResult<bool> {
+        tree_eq(slf.as_any(), other)
+    }
+
+    fn __iter__<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyIterator>> {
+        iter_values(slf.as_any())
+    }
+
+    fn depth(slf: &Bound<'_, Self>) -> usize {
+        depth(slf.as_any())
+    }
 
-        panic!("Node must receive two trees in its constructor")
+    fn size(slf: &Bound<'_, Self>) -> usize {
+        size(slf.as_any())
+    }
+
+    fn to_list<'py>(slf: &Bound<'py, Self>) -> Bound<'py, PyList> {
+        to_list(slf.as_any())
     }
 }
 
-impl BendType for Node {
-    fn to_bend(&self) -> ToBendResult {
-        let mut trees: Vec<imp::Expr> = vec![];
+// Dropping a Node would drop its subtrees recursively, so the subtrees that
+// are only owned by it are taken apart with an explicit stack instead.
+impl Drop for Node {
+    fn drop(&mut self) {
+        Python::with_gil(|py| {
+            let mut stack = vec![
+                take_subtree(py, &mut self.left),
+                take_subtree(py, &mut self.right),
+            ];
 
-        if let Some(left) = &self.left {
-            match left.to_bend() {
-                Ok(val) => trees.push(val),
-                Err(err) => return Err(err),
-            };
-        }
+            while let Some(tree) = stack.pop() {
+                if tree.get_refcnt(py) == 1 {
+                    if let Ok(node) = tree.bind(py).downcast::<Node>() {
+                        if let Ok(mut node) = node.try_borrow_mut() {
+                            stack.push(take_subtree(py, &mut node.left));
+                            stack.push(take_subtree(py, &mut node.right));
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
 
-        if let Some(right) = &self.right {
-            match right.to_bend() {
-                Ok(val) => trees.push(val),
-                Err(err) => return Err(err),
-            };
-        }
+fn take_subtree(py: Python<'_>, tree: &mut Py<PyAny>) -> Py<PyAny> {
+    std::mem::replace(tree, py.None())
+}
 
-        Ok(imp::Expr::Ctr {
-            name: fun::Name::new("Tree/Node"),
-            args: trees,
-            kwargs: vec![],
+impl BendType for Node {
+    fn to_bend(&self) -> ToBendResult {
+        Python::with_gil(|py| {
+            let left = tree_to_bend(self.left.bind(py))?;
+            let right = tree_to_bend(self.right.bind(py))?;
+            Ok(node_ctr(left, right))
         })
     }
 }
@@ -111,68 +171,278 @@ impl BendType for Node {
 #[derive(Clone, Debug)]
 #[pyclass(module = "benda", name = "Tree")]
 pub struct Tree {
-    pub leaf: Option<Leaf>,
-    pub node: Option<Node>,
+    // Either a Leaf or a Node.
+    pub root: Py<PyAny>,
 }
 
 impl BendType for Tree {
     fn to_bend(&self) -> ToBendResult {
-        if let Some(leaf) = &self.leaf {
-            return leaf.to_bend();
+        Python::with_gil(|py| tree_to_bend(self.root.bind(py)))
+    }
+}
+
+#[pymethods]
+impl Tree {
+    // Allows annotations like `Tree[float]`.
+    #[classmethod]
+    fn __class_getitem__(cls: &Bound<'_, PyType>, item: PyObject) -> PyResult<PyObject> {
+        let py = cls.py();
+        let generic_alias = py.import_bound("types")?.getattr("GenericAlias")?;
+        Ok(generic_alias.call1((cls, item))?.unbind())
+    }
+
+    #[new]
+    fn new(root: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(Self {
+            root: subtree(root)?,
+        })
+    }
+
+    // Builds a balanced tree with the values of the list as its leaves.
+    #[classmethod]
+    fn from_list(cls: &Bound<'_, PyType>, values: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let py = cls.py();
+
+        let mut level: Vec<Py<PyAny>> = vec![];
+        for value in values.iter()? {
+            level.push(Py::new(py, Leaf::__new__(value?.unbind()))?.into_any());
         }
 
-        if let Some(node) = &self.node {
-            return node.to_bend();
+        if level.is_empty() {
+            return Err(PyValueError::new_err("A Tree must have at least one Leaf"));
+        }
+
+        while level.len() > 1 {
+            let mut next: Vec<Py<PyAny>> = vec![];
+            let mut trees = level.into_iter();
+
+            while let Some(left) = trees.next() {
+                match trees.next() {
+                    Some(right) => next.push(Py::new(py, Node { left, right })?.into_any()),
+                    None => next.push(left),
+                }
+            }
+
+            level = next;
+        }
+
+        Ok(Self {
+            root: level.pop().unwrap(),
+        })
+    }
+
+    #[classattr]
+    fn __match_args__() -> (&'static str,) {
+        ("root",)
+    }
+
+    #[getter]
+    fn root(&self, py: Python<'_>) -> Py<PyAny> {
+        self.root.clone_ref(py)
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!("Tree({})", tree_repr(self.root.bind(py))?))
+    }
+
+    fn __eq__(&self, py: Python<'_>, other: &Bound<'_, PyAny>) -> PyResult<bool> {
+        match other.downcast::<Tree>() {
+            Ok(other) => tree_eq(self.root.bind(py), other.borrow().root.bind(py)),
+            Err(_) => Ok(false),
         }
+    }
 
-        todo!()
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        iter_values(self.root.bind(py))
+    }
+
+    fn depth(&self, py: Python<'_>) -> usize {
+        depth(self.root.bind(py))
+    }
+
+    fn size(&self, py: Python<'_>) -> usize {
+        size(self.root.bind(py))
+    }
+
+    fn to_list<'py>(&self, py: Python<'py>) -> Bound<'py, PyList> {
+        to_list(self.root.bind(py))
     }
 }
 
-#[derive(Debug)]
-pub enum TreeType {
-    Leaf,
-    Node,
-    Tree,
+fn leaf_ctr(value: imp::Expr) -> imp::Expr {
+    imp::Expr::Ctr {
+        name: fun::Name::new("Tree/Leaf"),
+        args: vec![value],
+        kwargs: vec![],
+    }
 }
 
-impl From<String> for TreeType {
-    fn from(value: String) -> Self {
-        match value.as_str() {
-            "benda.Leaf" => TreeType::Leaf,
-            "benda.Node" => TreeType::Node,
-            "benda.Tree" => TreeType::Tree,
-            _ => panic!("Tree __new__ must receive either Leaf or Node"),
-        }
+fn node_ctr(left: imp::Expr, right: imp::Expr) -> imp::Expr {
+    imp::Expr::Ctr {
+        name: fun::Name::new("Tree/Node"),
+        args: vec![left, right],
+        kwargs: vec![],
     }
 }
 
-#[pymethods]
-impl Tree {
-    #[new]
-    #[pyo3(signature = (*py_args))]
-    fn new(py_args: &Bound<'_, PyTuple>) -> Self {
-        for arg in py_args {
-            let t_type = arg.get_type();
-            let name = t_type.name().unwrap();
-            let tree_type = TreeType::from(name.to_string());
-
-            match tree_type {
-                TreeType::Leaf => {
-                    let leaf: Option<Leaf> = extract_inner(arg);
-                    if let Some(leaf) = leaf {
-                        return Self {
-                            leaf: Some(leaf),
-                            node: None,
-                        };
-                    }
+// Converts a Tree, Node or Leaf using an explicit stack instead of recursion,
+// so that very deep trees (like the ones shaped as linked lists) can be converted.
+pub fn tree_to_bend(root: &Bound<'_, PyAny>) -> ToBendResult {
+    enum Visit<'py> {
+        Tree(Bound<'py, PyAny>),
+        Node,
+    }
+
+    let py = root.py();
+    let mut stack: Vec<Visit> = vec![Visit::Tree(root.clone())];
+    let mut done: Vec<imp::Expr> = vec![];
+
+    while let Some(visit) = stack.pop() {
+        match visit {
+            Visit::Tree(tree) => {
+                if let Ok(node) = tree.downcast::<Node>() {
+                    let node = node.borrow();
+                    stack.push(Visit::Node);
+                    stack.push(Visit::Tree(node.right.bind(py).clone()));
+                    stack.push(Visit::Tree(node.left.bind(py).clone()));
+                } else if let Ok(leaf) = tree.downcast::<Leaf>() {
+                    let value = extract_type(leaf.borrow().value.bind(py).clone())?;
+                    done.push(leaf_ctr(value));
+                } else {
+                    stack.push(Visit::Tree(subtree(&tree)?.into_bound(py)));
                 }
-                _ => {
-                    panic!("Tree must receive a Leaf in constructor");
+            }
+            Visit::Node => {
+                let right = done.pop().unwrap();
+                let left = done.pop().unwrap();
+                done.push(node_ctr(left, right));
+            }
+        }
+    }
+
+    Ok(done.pop().unwrap())
+}
+
+// Writes a tree like `Node(Leaf(1), Leaf(2))`, using an explicit stack.
+fn tree_repr(root: &Bound<'_, PyAny>) -> PyResult<String> {
+    enum Visit<'py> {
+        Tree(Bound<'py, PyAny>),
+        Text(&'static str),
+    }
+
+    let py = root.py();
+    let mut stack: Vec<Visit> = vec![Visit::Tree(root.clone())];
+    let mut repr = String::new();
+
+    while let Some(visit) = stack.pop() {
+        match visit {
+            Visit::Tree(tree) => {
+                if let Ok(node) = tree.downcast::<Node>() {
+                    let node = node.borrow();
+                    repr.push_str("Node(");
+                    stack.push(Visit::Text(")"));
+                    stack.push(Visit::Tree(node.right.bind(py).clone()));
+                    stack.push(Visit::Text(", "));
+                    stack.push(Visit::Tree(node.left.bind(py).clone()));
+                } else {
+                    repr.push_str(&tree.repr()?.to_string());
                 }
             }
+            Visit::Text(text) => repr.push_str(text),
         }
+    }
+
+    Ok(repr)
+}
+
+// Checks that the argument is a Leaf or a Node, unwrapping it if it is a Tree.
+fn subtree(arg: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+    if arg.is_instance_of::<Leaf>() || arg.is_instance_of::<Node>() {
+        return Ok(arg.clone().unbind());
+    }
+
+    if let Ok(tree) = arg.downcast::<Tree>() {
+        return Ok(tree.borrow().root.clone_ref(arg.py()));
+    }
+
+    Err(PyTypeError::new_err(format!(
+        "A Tree must be built from Leaf and Node, not '{}'",
+        arg.get_type().name()?
+    )))
+}
 
-        todo!()
+// Visits the Leafs from left to right.
+fn leaves<'py>(root: &Bound<'py, PyAny>) -> Vec<Bound<'py, Leaf>> {
+    let mut found: Vec<Bound<'py, Leaf>> = vec![];
+    let mut stack: Vec<Bound<'py, PyAny>> = vec![root.clone()];
+
+    while let Some(tree) = stack.pop() {
+        if let Ok(node) = tree.downcast::<Node>() {
+            let node = node.borrow();
+            stack.push(node.right.bind(root.py()).clone());
+            stack.push(node.left.bind(root.py()).clone());
+        } else if let Ok(leaf) = tree.downcast_into::<Leaf>() {
+            found.push(leaf);
+        }
     }
+
+    found
+}
+
+fn to_list<'py>(root: &Bound<'py, PyAny>) -> Bound<'py, PyList> {
+    let values = leaves(root)
+        .into_iter()
+        .map(|leaf| leaf.borrow().value.clone_ref(root.py()));
+
+    PyList::new_bound(root.py(), values)
+}
+
+fn iter_values<'py>(root: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyIterator>> {
+    to_list(root).as_any().iter()
+}
+
+fn size(root: &Bound<'_, PyAny>) -> usize {
+    leaves(root).len()
+}
+
+// Depth of the deepest Leaf, a single Leaf has depth 0.
+fn depth(root: &Bound<'_, PyAny>) -> usize {
+    let mut max = 0;
+    let mut stack: Vec<(Bound<'_, PyAny>, usize)> = vec![(root.clone(), 0)];
+
+    while let Some((tree, depth)) = stack.pop() {
+        max = max.max(depth);
+
+        if let Ok(node) = tree.downcast::<Node>() {
+            let node = node.borrow();
+            stack.push((node.left.bind(root.py()).clone(), depth + 1));
+            stack.push((node.right.bind(root.py()).clone(), depth + 1));
+        }
+    }
+
+    max
+}
+
+// Structural equality, comparing the values of the Leafs with Python's `==`.
+fn tree_eq(left: &Bound<'_, PyAny>, right: &Bound<'_, PyAny>) -> PyResult<bool> {
+    let py = left.py();
+    let mut stack = vec![(left.clone(), right.clone())];
+
+    while let Some((left, right)) = stack.pop() {
+        match (left.downcast::<Node>(), right.downcast::<Node>()) {
+            (Ok(left), Ok(right)) => {
+                let (left, right) = (left.borrow(), right.borrow());
+                stack.push((left.left.bind(py).clone(), right.left.bind(py).clone()));
+                stack.push((left.right.bind(py).clone(), right.right.bind(py).clone()));
+            }
+            (Err(_), Err(_)) => {
+                if !left.eq(&right)? {
+                    return Ok(false);
+                }
+            }
+            _ => return Ok(false),
+        }
+    }
+
+    Ok(true)
 }
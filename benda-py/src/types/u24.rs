@@ -1,9 +1,13 @@
-use std::ops::{Add, Sub};
-
 use bend::imp;
-use pyo3::{pyclass, pymethods};
+use pyo3::{
+    exceptions::{PyValueError, PyZeroDivisionError},
+    prelude::*,
+    pyclass::CompareOp,
+    pymethods,
+    types::{PyFloat, PyLong},
+};
 
-use super::{BendType, ToBendResult};
+use super::{py_operator, BendType, ToBendResult};
 
 #[pyclass(module = "benda")]
 #[allow(non_camel_case_types)]
@@ -19,9 +23,8 @@ impl BendType for u24 {
 }
 
 impl u24 {
-    const MAX: u32 = 0xffffff;
+    pub const MAX: u32 = 0xffffff;
 
-    // TODO: Check if the masking is working properly
     pub fn new(value: u32) -> Self {
         Self(value & Self::MAX)
     }
@@ -55,11 +58,14 @@ impl From<u24> for u32 {
     }
 }
 
+// The operations follow HVM, which computes them on 32 bits and keeps the
+// lower 24 bits of the result.
+
 impl std::ops::Add for u24 {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
-        u24::new(self.0 + other.0)
+        u24::new(self.0.wrapping_add(other.0))
     }
 }
 
@@ -67,12 +73,88 @@ impl std::ops::Sub for u24 {
     type Output = Self;
 
     fn sub(self, other: Self) -> Self {
-        u24::new(self.0 - other.0)
+        u24::new(self.0.wrapping_sub(other.0))
+    }
+}
+
+impl std::ops::Mul for u24 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        u24::new(self.0.wrapping_mul(other.0))
     }
 }
 
-// TODO: Check for overflow on the operations
-// TODO: Implement tests for each operation comparing to Bend
+impl u24 {
+    pub fn div(self, other: Self) -> PyResult<Self> {
+        match self.0.checked_div(other.0) {
+            Some(val) => Ok(u24::new(val)),
+            None => Err(PyZeroDivisionError::new_err("u24 division by zero")),
+        }
+    }
+
+    pub fn rem(self, other: Self) -> PyResult<Self> {
+        match self.0.checked_rem(other.0) {
+            Some(val) => Ok(u24::new(val)),
+            None => Err(PyZeroDivisionError::new_err("u24 modulo by zero")),
+        }
+    }
+
+    // HVM has no integer power, so this is the power computed with the
+    // same wrapping multiplication.
+    pub fn pow(self, other: Self) -> Self {
+        u24::new(self.0.wrapping_pow(other.0))
+    }
+
+    pub fn shl(self, other: Self) -> Self {
+        u24::new(self.0 << (other.0 & 31))
+    }
+
+    pub fn shr(self, other: Self) -> Self {
+        u24::new(self.0 >> (other.0 & 31))
+    }
+
+    // Python ints are wrapped into 24 bits, other values can't be operands.
+    fn operand(other: &Bound<'_, PyAny>) -> Option<Self> {
+        if let Ok(other) = other.extract::<Self>() {
+            return Some(other);
+        }
+
+        let other = other.downcast::<PyLong>().ok()?;
+        let masked = other.bitand(Self::MAX).ok()?;
+        Some(u24::new(masked.extract().ok()?))
+    }
+
+    // Applies the operation when the other value is a u24 or an int, and
+    // falls back to the Python operation on ints for floats.
+    fn binary(
+        &self,
+        other: &Bound<'_, PyAny>,
+        name: &str,
+        reflected: bool,
+        op: impl Fn(Self, Self) -> PyResult<Self>,
+    ) -> PyResult<PyObject> {
+        let py = other.py();
+
+        if let Some(other) = Self::operand(other) {
+            let (a, b) = if reflected {
+                (other, *self)
+            } else {
+                (*self, other)
+            };
+            return Ok(op(a, b)?.into_py(py));
+        }
+
+        if other.is_instance_of::<PyFloat>() {
+            return match reflected {
+                true => py_operator(py, name, other, self.0),
+                false => py_operator(py, name, self.0, other),
+            };
+        }
+
+        Ok(py.NotImplemented())
+    }
+}
 
 #[pymethods]
 impl u24 {
@@ -81,12 +163,176 @@ impl u24 {
         u24::new(value)
     }
 
-    fn __add__(&self, other: &Self) -> Self {
-        u24::add(*self, *other)
+    fn __add__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "add", false, |a, b| Ok(a + b))
+    }
+
+    fn __radd__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "add", true, |a, b| Ok(a + b))
+    }
+
+    fn __sub__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "sub", false, |a, b| Ok(a - b))
+    }
+
+    fn __rsub__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "sub", true, |a, b| Ok(a - b))
+    }
+
+    fn __mul__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "mul", false, |a, b| Ok(a * b))
+    }
+
+    fn __rmul__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "mul", true, |a, b| Ok(a * b))
+    }
+
+    // Division in HVM is always an integer division.
+    fn __truediv__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "truediv", false, u24::div)
+    }
+
+    fn __rtruediv__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "truediv", true, u24::div)
+    }
+
+    fn __floordiv__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "floordiv", false, u24::div)
+    }
+
+    fn __rfloordiv__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "floordiv", true, u24::div)
+    }
+
+    fn __mod__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "mod", false, u24::rem)
+    }
+
+    fn __rmod__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "mod", true, u24::rem)
+    }
+
+    fn __pow__(
+        &self,
+        other: &Bound<'_, PyAny>,
+        modulo: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        if modulo.is_some() {
+            return Err(PyValueError::new_err("u24 pow() does not support a modulo"));
+        }
+        self.binary(other, "pow", false, |a, b| Ok(a.pow(b)))
+    }
+
+    fn __rpow__(
+        &self,
+        other: &Bound<'_, PyAny>,
+        modulo: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        if modulo.is_some() {
+            return Err(PyValueError::new_err("u24 pow() does not support a modulo"));
+        }
+        self.binary(other, "pow", true, |a, b| Ok(a.pow(b)))
+    }
+
+    fn __lshift__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "lshift", false, |a, b| Ok(a.shl(b)))
+    }
+
+    fn __rlshift__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "lshift", true, |a, b| Ok(a.shl(b)))
+    }
+
+    fn __rshift__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "rshift", false, |a, b| Ok(a.shr(b)))
+    }
+
+    fn __rrshift__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "rshift", true, |a, b| Ok(a.shr(b)))
+    }
+
+    fn __and__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "and_", false, |a, b| Ok(u24::new(a.0 & b.0)))
+    }
+
+    fn __rand__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "and_", true, |a, b| Ok(u24::new(a.0 & b.0)))
+    }
+
+    fn __or__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "or_", false, |a, b| Ok(u24::new(a.0 | b.0)))
+    }
+
+    fn __ror__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "or_", true, |a, b| Ok(u24::new(a.0 | b.0)))
+    }
+
+    fn __xor__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "xor", false, |a, b| Ok(u24::new(a.0 ^ b.0)))
+    }
+
+    fn __rxor__(&self, other: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        self.binary(other, "xor", true, |a, b| Ok(u24::new(a.0 ^ b.0)))
+    }
+
+    fn __neg__(&self) -> Self {
+        u24::new(0) - *self
+    }
+
+    fn __pos__(&self) -> Self {
+        *self
+    }
+
+    fn __abs__(&self) -> Self {
+        *self
+    }
+
+    fn __invert__(&self) -> Self {
+        u24::new(!self.0)
+    }
+
+    // Compares by value with u24s and with any Python number.
+    fn __richcmp__(&self, other: &Bound<'_, PyAny>, op: CompareOp) -> PyResult<PyObject> {
+        let py = other.py();
+        let other = match other.extract::<Self>() {
+            Ok(other) => other.0.into_py(py),
+            Err(_) => other.clone().unbind(),
+        };
+
+        let name = match op {
+            CompareOp::Lt => "lt",
+            CompareOp::Le => "le",
+            CompareOp::Eq => "eq",
+            CompareOp::Ne => "ne",
+            CompareOp::Gt => "gt",
+            CompareOp::Ge => "ge",
+        };
+
+        py_operator(py, name, self.0, other)
+    }
+
+    // Equal to the hash of the int with the same value, since they compare equal.
+    fn __hash__(&self, py: Python<'_>) -> PyResult<isize> {
+        self.0.into_py(py).bind(py).hash()
+    }
+
+    fn __bool__(&self) -> bool {
+        self.0 != 0
+    }
+
+    fn __int__(&self) -> u32 {
+        self.0
+    }
+
+    fn __index__(&self) -> u32 {
+        self.0
+    }
+
+    fn __float__(&self) -> f64 {
+        self.0 as f64
     }
 
-    fn __sub__(&self, other: &Self) -> Self {
-        u24::sub(*self, *other)
+    fn __repr__(&self) -> String {
+        format!("u24({})", self.0)
     }
 
     fn __str__(&self) -> String {
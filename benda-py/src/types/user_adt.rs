@@ -0,0 +1,256 @@
+use std::cell::RefCell;
+
+use bend::{
+    fun::{parser::Indent, Name},
+    imp::{self, parser::PyParser},
+};
+use pyo3::{
+    exceptions::{PySyntaxError, PyTypeError},
+    prelude::*,
+    pyclass, pymethods,
+    sync::GILProtected,
+    types::{PyDict, PyString, PyTuple, PyType},
+};
+
+use super::{enums, extract_type, modules::bend_name, BendType, ToBendResult};
+
+// A Python class registered with `benda.register_adt`.
+// It becomes a Bend ADT with a single constructor with the given fields,
+// named after the class and its module like the classes the parser finds.
+pub struct UserAdt {
+    pub class: Py<PyType>,
+    pub fields: Vec<(String, bool)>,
+    pub from_bend: Option<Py<PyAny>>,
+}
+
+static USER_ADTS: GILProtected<RefCell<Vec<UserAdt>>> = GILProtected::new(RefCell::new(vec![]));
+
+#[pyfunction]
+#[pyo3(signature = (cls, fields = None, from_bend = None))]
+pub fn register_adt(
+    cls: &Bound<'_, PyType>,
+    fields: Option<Vec<String>>,
+    from_bend: Option<Py<PyAny>>,
+) -> PyResult<()> {
+    let py = cls.py();
+    let name = cls.name()?.to_string();
+
+    let fields = match fields {
+        Some(fields) => fields,
+        None => class_fields(cls)?,
+    };
+
+    let annotations = cls
+        .getattr("__annotations__")
+        .ok()
+        .and_then(|ann| ann.downcast_into::<PyDict>().ok());
+
+    // A field annotated with the class itself is recursive.
+    let mut rec_fields: Vec<(String, bool)> = vec![];
+    for field in fields {
+        let annotation = match &annotations {
+            Some(ann) => ann.get_item(field.as_str())?,
+            None => None,
+        };
+
+        let rec = annotation.is_some_and(|ann| {
+            ann.is(cls)
+                || ann
+                    .downcast::<PyString>()
+                    .is_ok_and(|s| s.to_string() == name)
+        });
+
+        rec_fields.push((field, rec));
+    }
+
+    // Classes with the same name in different modules are different ADTs.
+    let mut adts = USER_ADTS.get(py).borrow_mut();
+    adts.retain(|adt| !adt.class.bind(py).is(cls));
+    adts.push(UserAdt {
+        class: cls.clone().unbind(),
+        fields: rec_fields,
+        from_bend,
+    });
+
+    Ok(())
+}
+
+// Fields of a class registered without an explicit field list, taken from the
+// dataclass fields or from `__match_args__`.
+fn class_fields(cls: &Bound<'_, PyType>) -> PyResult<Vec<String>> {
+    // `dataclasses.fields` leaves out the class variables.
+    if cls.hasattr("__dataclass_fields__")? {
+        let fields = cls
+            .py()
+            .import_bound("dataclasses")?
+            .call_method1("fields", (cls,))?;
+        return fields
+            .iter()?
+            .map(|field| field?.getattr("name")?.extract())
+            .collect();
+    }
+
+    if cls.hasattr("__match_args__")? {
+        return cls.getattr("__match_args__")?.extract();
+    }
+
+    Err(PyTypeError::new_err(format!(
+        "Could not find the fields of '{}', pass them with `fields=`",
+        cls.name()?
+    )))
+}
+
+fn with_user_adts<R>(py: Python<'_>, f: impl FnOnce(&[UserAdt]) -> R) -> R {
+    f(&USER_ADTS.get(py).borrow())
+}
+
+// The Bend name of a registered class, with its fields and whether they are recursive.
+pub type UserAdtDef = (String, Vec<(String, bool)>);
+
+// Returns the Bend name and fields of every registered class.
+pub fn user_adt_defs(py: Python<'_>) -> PyResult<Vec<UserAdtDef>> {
+    with_user_adts(py, |adts| {
+        adts.iter()
+            .map(|adt| Ok((bend_name(adt.class.bind(py))?, adt.fields.clone())))
+            .collect()
+    })
+}
+
+// Converts an instance of a registered class or of a dataclass into its constructor.
+pub fn extract_user_adt(arg: &Bound<'_, PyAny>) -> Option<ToBendResult> {
+    let py = arg.py();
+    let class = arg.get_type();
+
+    let registered = with_user_adts(py, |adts| {
+        adts.iter()
+            .find(|adt| adt.class.bind(py).is(&class))
+            .map(|adt| adt.fields.iter().map(|(field, _)| field.clone()).collect())
+    });
+
+    let (name, fields): (String, Vec<String>) = match registered {
+        Some(fields) => match bend_name(&class) {
+            Ok(name) => (name, fields),
+            Err(err) => return Some(Err(err)),
+        },
+        None if class.hasattr("__dataclass_fields__").unwrap_or(false) => {
+            match (bend_name(&class), class_fields(&class)) {
+                (Ok(name), Ok(fields)) => (name, fields),
+                (Err(err), _) | (_, Err(err)) => return Some(Err(err)),
+            }
+        }
+        None => return None,
+    };
+
+    let mut args: Vec<imp::Expr> = vec![];
+    for field in fields {
+        match arg.getattr(field.as_str()).and_then(extract_type) {
+            Ok(expr) => args.push(expr),
+            Err(err) => return Some(Err(err)),
+        }
+    }
+
+    Some(Ok(imp::Expr::Ctr {
+        name: Name::new(name),
+        args,
+        kwargs: vec![],
+    }))
+}
+
+// Bend code that can be returned by `__bend__`, for example
+// `benda.Term("Circle { radius: 2.0 }")`.
+#[derive(Clone, Debug)]
+#[pyclass(module = "benda", name = "Term")]
+pub struct Term {
+    code: String,
+}
+
+#[pymethods]
+impl Term {
+    #[new]
+    fn __new__(code: String) -> Self {
+        Self { code }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Term({:?})", self.code)
+    }
+}
+
+impl BendType for Term {
+    fn to_bend(&self) -> ToBendResult {
+        // Bend can only parse statements inside a definition, so the term is
+        // wrapped in a function that returns it.
+        let code = format!("term():\n  return {}\n", self.code);
+        let mut parser = PyParser::new(&code);
+
+        match parser.parse_def(Indent::Val(0)) {
+            Ok((
+                imp::Definition {
+                    body: imp::Stmt::Return { term },
+                    ..
+                },
+                _,
+            )) => Ok(*term),
+            Ok(_) => Err(PySyntaxError::new_err(format!(
+                "'{}' is not a Bend term",
+                self.code
+            ))),
+            Err(err) => Err(PySyntaxError::new_err(err)),
+        }
+    }
+}
+
+// Objects can describe their own Bend representation with a `__bend__`
+// method, returning a `benda.Term` or any other convertible value.
+pub fn extract_bend_method(arg: &Bound<'_, PyAny>) -> Option<ToBendResult> {
+    if !arg.hasattr("__bend__").unwrap_or(false) || arg.is_instance_of::<PyType>() {
+        return None;
+    }
+
+    Some(arg.call_method0("__bend__").and_then(|term| {
+        // Another `__bend__` would be called again, maybe without end.
+        if term.hasattr("__bend__")? && !term.is_instance_of::<PyType>() {
+            return Err(PyTypeError::new_err(format!(
+                "'__bend__' of '{}' returned an object with its own '__bend__', return a benda.Term or a plain value instead",
+                arg.get_type().name()?
+            )));
+        }
+        extract_type(term)
+    }))
+}
+
+// Builds the Python value for a constructor read back from Bend. The
+// `from_bend` given to `register_adt` has priority over `__from_bend__`.
+pub fn from_bend<'py>(class: &Bound<'py, PyAny>, args: Bound<'py, PyTuple>) -> PyResult<Py<PyAny>> {
+    let py = class.py();
+
+    let registered = with_user_adts(py, |adts| {
+        adts.iter()
+            .find(|adt| adt.class.bind(py).is(class))
+            .and_then(|adt| adt.from_bend.as_ref().map(|f| f.clone_ref(py)))
+    });
+
+    if let Some(from_bend) = registered {
+        return Ok(from_bend.bind(py).call1(args)?.unbind());
+    }
+
+    // Enum members are found instead of their class.
+    if enums::is_enum(&class.get_type()) {
+        return Ok(class.clone().unbind());
+    }
+
+    if class.hasattr("__from_bend__")? {
+        return Ok(class.call_method1("__from_bend__", args)?.unbind());
+    }
+
+    Ok(class.call1(args)?.unbind())
+}
+
+// Finds a registered class by its ADT name.
+pub fn find_user_class<'py>(py: Python<'py>, name: &str) -> Option<Bound<'py, PyAny>> {
+    with_user_adts(py, |adts| {
+        adts.iter()
+            .find(|adt| bend_name(adt.class.bind(py)).is_ok_and(|adt_name| adt_name == name))
+            .map(|adt| adt.class.bind(py).clone().into_any())
+    })
+}